@@ -1,7 +1,7 @@
 //! This simple example involves a world of particles that have both position
 //! and velocity
 
-use eks::{component, entity, map, map_mut, Entity, World};
+use eks::{component, entity, map, map_mut, Entity, Schedule, World};
 
 // Set up components
 component! {
@@ -26,33 +26,47 @@ fn main() {
     world.insert(particle(50.0, -8.0, '$'));
     world.insert(particle(-30.0, 4.0, '&'));
 
+    // Draw the particles, then move them
+    let mut schedule = Schedule::new()
+        .with_stage("draw")
+        .with_stage("update")
+        .with_system("draw", draw)
+        .with_system("update", update);
+
     // Loop 20 times
     for _ in 0..20 {
-        // Ititialize some empty space for this iteration
-        let mut space: Vec<char> = (0..60).map(|_| ' ').collect();
-
-        // Put sprites where the particles are
-        for (pos, sprite) in map!(Pos, Sprite in world) {
-            if *pos >= 0.0 {
-                let upos = *pos as usize;
-                if upos < space.len() {
-                    space[upos] = *sprite;
-                }
-            }
-        }
+        schedule.run(&mut world);
 
-        // Draw the space
-        for c in space {
-            print!("{}", c);
-        }
-        println!();
+        // Wait for a quarter second
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+}
 
-        // Update the particle positions
-        for (pos, vel) in map_mut!(Pos, Vel in world) {
-            *pos += *vel;
+/// System for drawing the particles
+fn draw(world: &mut World<Particle>) {
+    // Ititialize some empty space for this iteration
+    let mut space: Vec<char> = (0..60).map(|_| ' ').collect();
+
+    // Put sprites where the particles are
+    for (pos, sprite) in map!(Pos, Sprite in world) {
+        if *pos >= 0.0 {
+            let upos = *pos as usize;
+            if upos < space.len() {
+                space[upos] = *sprite;
+            }
         }
+    }
 
-        // Wait for a quarter second
-        std::thread::sleep(std::time::Duration::from_millis(250));
+    // Draw the space
+    for c in space {
+        print!("{}", c);
+    }
+    println!();
+}
+
+/// System for updating the particle positions
+fn update(world: &mut World<Particle>) {
+    for (pos, vel) in map_mut!(Pos, Vel in world) {
+        *pos += *vel;
     }
 }
@@ -0,0 +1,549 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::{BTreeMap, BTreeSet},
+};
+
+#[cfg(feature = "f_rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    Commands, Component, ComponentSet, Entity, EventReader, Events, Filter, Id, IdStatus, Relation,
+    World,
+};
+
+/**
+The components and resources that a system reads and writes
+
+Two systems conflict if one of them writes a component or resource
+that the other one reads or writes.
+
+# Example
+```
+use eks::*;
+
+component! { Position: isize, Speed: isize }
+
+let movement = Access::new().read::<Speed>().write::<Position>();
+let brakes = Access::new().write::<Speed>().read_resource::<f32>();
+assert_eq!(vec!["Speed"], movement.conflicts(&brakes));
+
+let timer = Access::new().write_resource::<f32>();
+assert_eq!(vec!["f32"], timer.conflicts(&brakes));
+```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Access {
+    reads: BTreeSet<&'static str>,
+    writes: BTreeSet<&'static str>,
+    resource_reads: BTreeMap<TypeId, &'static str>,
+    resource_writes: BTreeMap<TypeId, &'static str>,
+}
+
+impl Access {
+    /// Create a new `Access` that does not access any components
+    pub fn new() -> Access {
+        Access::default()
+    }
+    /// Declare that a component is read
+    pub fn read<T>(self) -> Self
+    where
+        T: Component,
+    {
+        self.read_name(T::AS_STR)
+    }
+    /// Declare that a component is written, which also allows reading it
+    pub fn write<T>(self) -> Self
+    where
+        T: Component,
+    {
+        self.write_name(T::AS_STR)
+    }
+    /// Declare that a component is read by name
+    pub(crate) fn read_name(mut self, name: &'static str) -> Self {
+        if !self.writes.contains(name) {
+            self.reads.insert(name);
+        }
+        self
+    }
+    /// Declare that a component is written by name
+    pub(crate) fn write_name(mut self, name: &'static str) -> Self {
+        self.reads.remove(name);
+        self.writes.insert(name);
+        self
+    }
+    /// Declare that a resource is read
+    pub fn read_resource<R>(mut self) -> Self
+    where
+        R: 'static,
+    {
+        if !self.resource_writes.contains_key(&TypeId::of::<R>()) {
+            self.resource_reads
+                .insert(TypeId::of::<R>(), type_name::<R>());
+        }
+        self
+    }
+    /// Declare that a resource is written
+    pub fn write_resource<R>(mut self) -> Self
+    where
+        R: 'static,
+    {
+        self.resource_reads.remove(&TypeId::of::<R>());
+        self.resource_writes
+            .insert(TypeId::of::<R>(), type_name::<R>());
+        self
+    }
+    /// Iterates through the names of the components that are only read
+    pub fn reads(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.reads.iter().copied()
+    }
+    /// Iterates through the names of the components that are written
+    pub fn writes(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.writes.iter().copied()
+    }
+    /// Check if a component can be read
+    pub fn can_read(&self, name: &str) -> bool {
+        self.reads.contains(name) || self.writes.contains(name)
+    }
+    /// Check if a component can be written
+    pub fn can_write(&self, name: &str) -> bool {
+        self.writes.contains(name)
+    }
+    /// Check if a resource is declared as only read
+    pub fn reads_resource<R>(&self) -> bool
+    where
+        R: 'static,
+    {
+        self.resource_reads.contains_key(&TypeId::of::<R>())
+    }
+    /// Check if a resource is declared as written
+    pub fn writes_resource<R>(&self) -> bool
+    where
+        R: 'static,
+    {
+        self.resource_writes.contains_key(&TypeId::of::<R>())
+    }
+    /// Get the names of the components and resources that make
+    /// this `Access` conflict with another one
+    pub fn conflicts(&self, other: &Access) -> Vec<&'static str> {
+        let mut names: Vec<_> = self
+            .writes
+            .iter()
+            .filter(|name| other.can_read(name))
+            .chain(
+                other
+                    .writes
+                    .iter()
+                    .filter(|name| self.reads.contains(*name)),
+            )
+            .copied()
+            .collect();
+        names.sort_unstable();
+        let mut resources: Vec<_> = self
+            .resource_writes
+            .iter()
+            .filter(|(id, _)| other.resource_reads.contains_key(id))
+            .chain(
+                self.resource_writes
+                    .iter()
+                    .filter(|(id, _)| other.resource_writes.contains_key(id)),
+            )
+            .chain(
+                other
+                    .resource_writes
+                    .iter()
+                    .filter(|(id, _)| self.resource_reads.contains_key(id)),
+            )
+            .map(|(_, name)| *name)
+            .collect();
+        resources.sort_unstable();
+        names.extend(resources);
+        names
+    }
+    /// Check if this `Access` conflicts with another one
+    pub fn conflicts_with(&self, other: &Access) -> bool {
+        !self.conflicts(other).is_empty()
+    }
+}
+
+/**
+A view of a `World` that only allows the accesses declared by a system
+
+This is what a `ParSystem` runs on. Several `WorldView`s of the same `World`
+may be in use at the same time on different threads, so every query
+and resource access is checked against the `Access` and panics if it
+is not allowed.
+
+`map!` and `map_mut!` work on a `WorldView` the same way they work on a `World`,
+except that `Added` and `Changed` compare against the tick the system last ran at.
+*/
+pub struct WorldView<'w, C> {
+    world: &'w World<C>,
+    access: &'w Access,
+    commands: &'w mut Commands<C>,
+    /// The tick the system last ran at
+    last_run: u64,
+}
+
+impl<'w, C> WorldView<'w, C> {
+    /**
+    Create a view of a `World` with some `Access`
+
+    The caller must make sure that no other view that conflicts
+    with `access`, and no mutable reference to the `World`,
+    is in use at the same time.
+    */
+    pub(crate) fn new(
+        world: &'w World<C>,
+        access: &'w Access,
+        commands: &'w mut Commands<C>,
+        last_run: u64,
+    ) -> WorldView<'w, C> {
+        WorldView {
+            world,
+            access,
+            commands,
+            last_run,
+        }
+    }
+    /// Get the `Access` of this view
+    pub fn access(&self) -> &Access {
+        self.access
+    }
+    /**
+    Get the system's `Commands`
+
+    The `Schedule` applies them to the `World` at the end of
+    the stage, in the order the systems were added.
+    */
+    pub fn commands(&mut self) -> &mut Commands<C> {
+        self.commands
+    }
+    /// Same as `World::reserve_id`
+    pub fn reserve_id(&self) -> Id {
+        self.world.reserve_id()
+    }
+    /// Record spawning an `Entity` in the system's `Commands`, and return the `Id` it will have
+    pub fn spawn(&mut self, entity: Entity<C>) -> Id {
+        self.commands.spawn(self.world, entity)
+    }
+    /// Get the number of `Entity`s in the `World`
+    pub fn len(&self) -> usize {
+        self.world.len()
+    }
+    /// Check if the `World` is empty
+    pub fn is_empty(&self) -> bool {
+        self.world.is_empty()
+    }
+    /// Check if the `World` contains an `Entity` with the given `Id`
+    pub fn contains(&self, id: Id) -> bool {
+        self.world.contains(id)
+    }
+    /// Same as `World::status`
+    pub fn status(&self, id: Id) -> IdStatus {
+        self.world.status(id)
+    }
+    /// Same as `World::parent`
+    pub fn parent(&self, id: Id) -> Option<Id> {
+        self.world.parent(id)
+    }
+    /// Same as `World::children`
+    pub fn children(&self, id: Id) -> &'w [Id] {
+        self.world.children(id)
+    }
+    /// Same as `World::ancestors`
+    pub fn ancestors(&self, id: Id) -> impl Iterator<Item = Id> + 'w {
+        self.world.ancestors(id)
+    }
+    /// Same as `World::descendants`
+    pub fn descendants(&self, id: Id) -> impl Iterator<Item = Id> + 'w {
+        self.world.descendants(id)
+    }
+    /// Same as `World::targets`
+    pub fn targets<R>(&self, source: Id) -> &'w [Id]
+    where
+        R: Relation,
+    {
+        self.world.targets::<R>(source)
+    }
+    /// Same as `World::sources`
+    pub fn sources<R>(&self, target: Id) -> &'w [Id]
+    where
+        R: Relation,
+    {
+        self.world.sources::<R>(target)
+    }
+    /**
+    Same as `World::query`
+
+    # Panics
+
+    Panics if any of the components are not declared as read or written
+    */
+    pub fn query<'a, Q>(&'a self) -> impl Iterator<Item = Q::Ref> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+    {
+        self.query_filtered::<Q, ()>()
+    }
+    /**
+    Same as `World::query_mut`
+
+    # Panics
+
+    Panics if any of the components are not declared as written,
+    except for `Read` ones, which only have to be declared as read
+    */
+    pub fn query_mut<'a, Q>(&'a mut self) -> impl Iterator<Item = Q::Mut> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+    {
+        self.query_mut_filtered::<Q, ()>()
+    }
+    /**
+    Same as `World::query_filtered`, but filters that depend on ticks
+    compare against the tick the system last ran at
+
+    # Panics
+
+    Panics if any of the components are not declared as read or written
+    */
+    pub fn query_filtered<'a, Q, F>(&'a self) -> impl Iterator<Item = Q::Ref> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.check::<F>(&Q::names(), &[]);
+        self.world.query_since::<Q, F>(None, self.last_run)
+    }
+    /**
+    Same as `World::query_mut_filtered`, but filters that depend on ticks
+    compare against the tick the system last ran at
+
+    # Panics
+
+    Panics if any of the components are not declared as written,
+    except for `Read` ones, or if any of the `Read` or the filter's
+    components are not declared as read or written
+    */
+    pub fn query_mut_filtered<'a, Q, F>(&'a mut self) -> impl Iterator<Item = Q::Mut> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Q::DISTINCT;
+        self.check::<F>(&Q::names(), &Q::writes());
+        // Safety: the view is borrowed mutably, no view that conflicts with
+        // this one is in use, and `DISTINCT` guarantees that the components
+        // are all different
+        unsafe { self.world.query_unchecked::<Q, F>(None, self.last_run) }
+    }
+    /// Parallel version of `WorldView::query`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_query<'a, Q>(&'a self) -> impl ParallelIterator<Item = Q::Ref> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Ref: Send,
+    {
+        self.par_query_filtered::<Q, ()>()
+    }
+    /// Parallel version of `WorldView::query_mut`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_query_mut<'a, Q>(&'a mut self) -> impl ParallelIterator<Item = Q::Mut> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Mut: Send,
+    {
+        self.par_query_mut_filtered::<Q, ()>()
+    }
+    /// Parallel version of `WorldView::query_filtered`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_query_filtered<'a, Q, F>(&'a self) -> impl ParallelIterator<Item = Q::Ref> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Ref: Send,
+        F: Filter<C> + 'a,
+    {
+        self.check::<F>(&Q::names(), &[]);
+        self.world.par_query_since::<Q, F>(None, self.last_run)
+    }
+    /// Parallel version of `WorldView::query_mut_filtered`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_query_mut_filtered<'a, Q, F>(
+        &'a mut self,
+    ) -> impl ParallelIterator<Item = Q::Mut> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Mut: Send,
+        F: Filter<C> + 'a,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Q::DISTINCT;
+        self.check::<F>(&Q::names(), &Q::writes());
+        // Safety: see `WorldView::query_mut_filtered`
+        unsafe { self.world.par_query_unchecked::<Q, F>(None, self.last_run) }
+    }
+    /**
+    Same as `World::iter_with`, but iterates over the `Id`s of the `Entity`s
+
+    An `EntityRef` can read every component of its `Entity`, including the
+    ones the system has not declared, so a view only gives out `Id`s.
+    This is what `tags!(C1, C2, ... in VIEW)` expands to.
+
+    # Panics
+
+    Panics if any of the components are not declared as read or written
+    */
+    pub fn iter_with<'a, Q>(&'a self) -> impl Iterator<Item = Id> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+    {
+        self.iter_with_filtered::<Q, ()>()
+    }
+    /**
+    Same as `WorldView::iter_with`, but only for `Entity`s that match the `Filter` `F`
+
+    Filters that depend on ticks compare against the tick the system last ran at.
+
+    # Panics
+
+    Panics if any of the components are not declared as read or written
+    */
+    pub fn iter_with_filtered<'a, Q, F>(&'a self) -> impl Iterator<Item = Id> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.check::<F>(&Q::names(), &[]);
+        self.world
+            .iter_with_since::<Q, F>(None, self.last_run)
+            .map(|entity| entity.id())
+    }
+    /// Parallel version of `WorldView::iter_with`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_iter_with<'a, Q>(&'a self) -> impl ParallelIterator<Item = Id> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+    {
+        self.par_iter_with_filtered::<Q, ()>()
+    }
+    /// Parallel version of `WorldView::iter_with_filtered`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_iter_with_filtered<'a, Q, F>(&'a self) -> impl ParallelIterator<Item = Id> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.check::<F>(&Q::names(), &[]);
+        self.world
+            .par_iter_with_since::<Q, F>(None, self.last_run)
+            .map(|entity| entity.id())
+    }
+    /// Same as `World::removed`, but for removals since the system last ran
+    pub fn removed<T>(&self) -> impl Iterator<Item = Id> + 'w
+    where
+        T: Component<Enum = C>,
+    {
+        self.world.removed_since::<T>(self.last_run)
+    }
+    /**
+    Get a reference to a resource that is declared as only read
+
+    The reference can be held while querying components.
+
+    # Panics
+
+    Panics if the resource is not declared with `Access::read_resource`.
+    Resources declared with `Access::write_resource` are accessed with
+    `WorldView::resource_mut`.
+    */
+    pub fn resource<R>(&self) -> Option<&'w R>
+    where
+        R: 'static,
+    {
+        if !self.access.reads_resource::<R>() {
+            panic!(
+                "Resource {} is not declared as read by the system",
+                type_name::<R>()
+            );
+        }
+        self.world.resource::<R>()
+    }
+    /**
+    Get a mutable reference to a resource
+
+    # Panics
+
+    Panics if the resource is not declared with `Access::write_resource`
+    */
+    pub fn resource_mut<R>(&mut self) -> Option<&mut R>
+    where
+        R: 'static,
+    {
+        if !self.access.writes_resource::<R>() {
+            panic!(
+                "Resource {} is not declared as written by the system",
+                type_name::<R>()
+            );
+        }
+        // Safety: the view is borrowed mutably, and no view
+        // that conflicts with this one is in use
+        unsafe { self.world.resource_unchecked_mut::<R>() }
+    }
+    /**
+    Iterates through the events of type `E` that the reader has not read yet
+
+    # Panics
+
+    Panics if `Events<E>` is not declared with `Access::read_resource`
+    */
+    pub fn read_events<E>(&self, reader: &mut EventReader<E>) -> impl Iterator<Item = &'w E>
+    where
+        E: 'static,
+    {
+        self.resource::<Events<E>>()
+            .map(|events| reader.read(events))
+            .into_iter()
+            .flatten()
+    }
+    /// Get the `World` the view is of
+    pub(crate) fn world(&self) -> &'w World<C> {
+        self.world
+    }
+    /// Get the tick the system last ran at
+    pub(crate) fn last_run(&self) -> u64 {
+        self.last_run
+    }
+    /**
+    Check that the components in `names` and the ones `F` looks at
+    can be read, and that the components in `writes` can be written
+
+    # Panics
+
+    Panics if any of them can't
+    */
+    pub(crate) fn check<F>(&self, names: &[&str], writes: &[&str])
+    where
+        F: Filter<C>,
+    {
+        for name in F::names().iter().chain(names) {
+            if !self.access.can_read(name) {
+                panic!("Component {} is not declared as read by the system", name);
+            }
+        }
+        for name in writes {
+            if !self.access.can_write(name) {
+                panic!(
+                    "Component {} is not declared as written by the system",
+                    name
+                );
+            }
+        }
+    }
+}
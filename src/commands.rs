@@ -0,0 +1,270 @@
+use std::{fmt, iter::FromIterator};
+
+#[cfg(feature = "f_rayon")]
+use rayon::prelude::*;
+
+use crate::{Component, Entity, Id, Relation, World};
+
+/// An arbitrary recorded operation
+type Operation<C> = Box<dyn FnOnce(&mut World<C>) + Send>;
+
+/// A recorded operation
+enum Command<C> {
+    Spawn(Id, Entity<C>),
+    Run(Operation<C>),
+}
+
+/**
+A buffer of structural changes to apply to a `World` later
+
+A `World` cannot be changed while it is being iterated, so the changes
+are recorded in a `Commands` and applied afterwards with `Commands::apply`.
+Operations on `Id`s that are not in the `World` when the `Commands`
+are applied do nothing.
+
+`Commands::spawn` reserves the `Id` of the new `Entity` right away,
+so later operations in the same `Commands` can refer to it.
+
+`Commands` can be collected from iterators and rayon parallel iterators
+of `Commands`. The buffers are joined in the order of the iterator,
+so the result doesn't depend on how the work was split between threads.
+
+# Example
+```
+use eks::*;
+
+component! { Health: i32, Dead: () }
+
+let mut world = World::new();
+world.insert(entity! { Health: 10 });
+world.insert(entity! { Health: -5 });
+
+let mut commands = Commands::new();
+for entity in tags!(Health in world) {
+    if entity[Health] <= 0 {
+        commands.add::<Dead>(entity.id(), ());
+    }
+}
+let id = commands.spawn(&world, entity! { Health: 20 });
+commands.add::<Dead>(id, ());
+commands.apply(&mut world);
+
+assert_eq!(3, world.len());
+assert_eq!(2, tags!(Dead in world).count());
+assert_eq!(20, world[id][Health]);
+```
+*/
+pub struct Commands<C> {
+    commands: Vec<Command<C>>,
+}
+
+impl<C> Default for Commands<C> {
+    fn default() -> Self {
+        Commands::new()
+    }
+}
+
+impl<C> fmt::Debug for Commands<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Commands")
+            .field("len", &self.commands.len())
+            .finish()
+    }
+}
+
+impl<C> Commands<C> {
+    /// Create an empty `Commands`
+    pub fn new() -> Commands<C> {
+        Commands {
+            commands: Vec::new(),
+        }
+    }
+    /// Get the number of recorded operations
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+    /// Check if no operations have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+    /**
+    Record adding an `Entity` to the `World`, and return the `Id` it will have
+
+    The `Id` is reserved with `World::reserve_id`, so the `Commands`
+    must be applied to the same `World`.
+    */
+    pub fn spawn(&mut self, world: &World<C>, entity: Entity<C>) -> Id {
+        let id = world.reserve_id();
+        self.spawn_reserved(id, entity);
+        id
+    }
+    /**
+    Record adding an `Entity` to the `World` under an `Id` reserved with `World::reserve_id`
+
+    # Panics
+
+    Applying the `Commands` panics if the `Id` is not reserved in the `World`
+    they are applied to, or if an `Entity` was already added under it.
+    */
+    pub fn spawn_reserved(&mut self, id: Id, entity: Entity<C>) -> &mut Self {
+        self.commands.push(Command::Spawn(id, entity));
+        self
+    }
+    /// Move all the operations from another `Commands` to the end of this one
+    pub fn append(&mut self, other: &mut Commands<C>) -> &mut Self {
+        self.commands.append(&mut other.commands);
+        self
+    }
+    /**
+    Apply the operations to a `World` in the order they were recorded
+
+    Returns the `Id`s of the spawned `Entity`s in the order they were spawned.
+    */
+    pub fn apply(self, world: &mut World<C>) -> Vec<Id> {
+        let mut spawned = Vec::new();
+        for command in self.commands {
+            match command {
+                Command::Spawn(id, entity) => {
+                    world.insert_reserved(id, entity);
+                    spawned.push(id);
+                }
+                Command::Run(f) => f(world),
+            }
+        }
+        spawned
+    }
+}
+
+impl<C: 'static> Commands<C> {
+    /// Record an arbitrary operation on the `World`
+    pub fn push<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut World<C>) + Send + 'static,
+    {
+        self.commands.push(Command::Run(Box::new(f)));
+        self
+    }
+    /// Record removing the `Entity` with the given `Id`
+    pub fn despawn(&mut self, id: Id) -> &mut Self {
+        self.push(move |world| {
+            world.remove(id);
+        })
+    }
+    /// Record removing the `Entity` with the given `Id` and all of its descendants
+    pub fn despawn_recursive(&mut self, id: Id) -> &mut Self {
+        self.push(move |world| {
+            world.remove_recursive(id);
+        })
+    }
+    /// Record making the `Entity` with the `Id` `parent` the parent of the one with the `Id` `child`
+    pub fn set_parent(&mut self, child: Id, parent: Id) -> &mut Self {
+        self.push(move |world| {
+            world.set_parent(child, parent);
+        })
+    }
+    /// Record adding the relation `R` from `source` to `target`
+    pub fn relate<R>(&mut self, source: Id, target: Id) -> &mut Self
+    where
+        R: Relation,
+    {
+        self.push(move |world| {
+            world.relate::<R>(source, target);
+        })
+    }
+    /// Record removing the relation `R` from `source` to `target`
+    pub fn unrelate<R>(&mut self, source: Id, target: Id) -> &mut Self
+    where
+        R: Relation,
+    {
+        self.push(move |world| {
+            world.unrelate::<R>(source, target);
+        })
+    }
+    /// Record sending an event
+    pub fn send_event<E>(&mut self, event: E) -> &mut Self
+    where
+        E: Send + Sync + 'static,
+    {
+        self.push(move |world| world.send_event(event))
+    }
+    /// Record triggering an event on the `Entity` with the `Id` `target`
+    pub fn trigger<E>(&mut self, target: Id, event: E) -> &mut Self
+    where
+        E: Send + 'static,
+    {
+        self.push(move |world| world.trigger(target, event))
+    }
+    /// Record adding a component to the `Entity` with the given `Id`
+    pub fn add<T>(&mut self, id: Id, value: T::Type) -> &mut Self
+    where
+        T: Component<Enum = C>,
+        T::Type: Send + 'static,
+    {
+        self.push(move |world| {
+            if let Some(mut entity) = world.get_mut(id) {
+                entity.add::<T>(value);
+            }
+        })
+    }
+    /// Record removing a component from the `Entity` with the given `Id`
+    pub fn remove<T>(&mut self, id: Id) -> &mut Self
+    where
+        T: Component<Enum = C> + 'static,
+    {
+        self.push(move |world| {
+            if let Some(mut entity) = world.get_mut(id) {
+                entity.remove::<T>();
+            }
+        })
+    }
+}
+
+impl<C> Extend<Commands<C>> for Commands<C> {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Commands<C>>,
+    {
+        for mut commands in iter {
+            self.append(&mut commands);
+        }
+    }
+}
+
+impl<C> FromIterator<Commands<C>> for Commands<C> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Commands<C>>,
+    {
+        let mut commands = Commands::new();
+        commands.extend(iter);
+        commands
+    }
+}
+
+#[cfg(feature = "f_rayon")]
+impl<C: Send> ParallelExtend<Commands<C>> for Commands<C> {
+    fn par_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = Commands<C>>,
+    {
+        let mut commands = Commands::from_par_iter(par_iter);
+        self.append(&mut commands);
+    }
+}
+
+#[cfg(feature = "f_rayon")]
+impl<C: Send> FromParallelIterator<Commands<C>> for Commands<C> {
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = Commands<C>>,
+    {
+        // `reduce` keeps the order of the items,
+        // so the result is the same as a sequential join
+        par_iter
+            .into_par_iter()
+            .reduce(Commands::new, |mut a, mut b| {
+                a.append(&mut b);
+                a
+            })
+    }
+}
@@ -0,0 +1,150 @@
+use std::marker::PhantomData;
+
+/**
+A queue of events of a single type, stored in a `World` as a resource
+
+Events are sent with `World::send_event` or `Commands::send_event`
+and read with an `EventReader`. They are double-buffered: `World::update_events`,
+which a `Schedule` calls at the end of every run, drops the events from
+before the previous update. An event sent during one run of a `Schedule`
+can therefore be read by every system until the end of the next run,
+so each system that runs once per run sees it exactly once.
+
+To read events in a system with declared `Access`, declare
+`Access::read_resource::<Events<E>>`.
+
+# Example
+```
+use eks::*;
+
+component! { Health: i32 }
+
+struct Died(Id);
+
+let mut deaths = EventReader::<Died>::new();
+let mut schedule = Schedule::new()
+    .with_stage("update")
+    .with_system("update", |world: &mut World<Comp>| {
+        let dead: Vec<Id> = map!(id, Health in world)
+            .filter(|(_, health)| **health <= 0)
+            .map(|(id, _)| id)
+            .collect();
+        for id in dead {
+            world.remove(id);
+            world.send_event(Died(id));
+        }
+    })
+    .with_system("update", move |world: &mut World<Comp>| {
+        let died: Vec<Id> = world.read_events(&mut deaths).map(|Died(id)| *id).collect();
+        world.insert_resource(died);
+    });
+
+let mut world = World::new();
+let id = world.insert(entity! { Health: 0 });
+schedule.run(&mut world);
+assert_eq!(Some(&vec![id]), world.resource::<Vec<Id>>());
+```
+*/
+#[derive(Debug, Clone)]
+pub struct Events<E> {
+    /// The events sent before the last update
+    previous: Vec<E>,
+    /// The events sent since the last update
+    current: Vec<E>,
+    /// The number of events sent before the first one in `previous`
+    start: usize,
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Events::new()
+    }
+}
+
+impl<E> Events<E> {
+    /// Create an empty queue
+    pub fn new() -> Events<E> {
+        Events {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+        }
+    }
+    /// Add an event to the queue
+    pub fn send(&mut self, event: E) {
+        self.current.push(event);
+    }
+    /// Get the number of events in the queue
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+    /// Check if the queue has no events
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Iterates through every event in the queue in the order they were sent
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.previous.iter().chain(&self.current)
+    }
+    /// Drop the events from before the last update and keep the ones sent since
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+    /// Remove every event from the queue
+    pub fn clear(&mut self) {
+        self.start += self.len();
+        self.previous.clear();
+        self.current.clear();
+    }
+    /// Get the number of events sent in total
+    fn end(&self) -> usize {
+        self.start + self.len()
+    }
+}
+
+/**
+Reads the events of a single type, keeping track of which ones it has read
+
+Every reader has its own position in the queue, so any number of
+readers can each read every event once. A reader that is not used
+for two updates of the `Events` misses the events that were dropped.
+*/
+#[derive(Debug)]
+pub struct EventReader<E> {
+    /// The number of events sent before the first one that has not been read
+    read: usize,
+    pd: PhantomData<fn() -> E>,
+}
+
+impl<E> Default for EventReader<E> {
+    fn default() -> Self {
+        EventReader::new()
+    }
+}
+
+impl<E> Clone for EventReader<E> {
+    fn clone(&self) -> Self {
+        EventReader {
+            read: self.read,
+            pd: PhantomData,
+        }
+    }
+}
+
+impl<E> EventReader<E> {
+    /// Create a reader that reads every event still in the queue
+    pub fn new() -> EventReader<E> {
+        EventReader {
+            read: 0,
+            pd: PhantomData,
+        }
+    }
+    /// Iterates through the events the reader has not read yet and marks them as read
+    pub fn read<'a>(&mut self, events: &'a Events<E>) -> impl Iterator<Item = &'a E> {
+        let skip = self.read.saturating_sub(events.start);
+        self.read = events.end();
+        events.iter().skip(skip)
+    }
+}
@@ -12,11 +12,25 @@ declaration and marked with either `#unit` or `#variant`:
 * Attributes marked `#unit` will be applied to the generated unit struct for that component.
 * Attributes marked `#variant` will be applied to the component's variant in the generated `Component` `enum`
 
+A component's storage can be chosen by putting `#storage(table)` or `#storage(sparse)` right
+before its declaration. See [`Storage`](../enum.Storage.html) for the difference.
+The version that snapshots record for a component is set the same way with `#version(n)`,
+after `#storage`. See [`Component::VERSION`](../trait.Component.html#associatedconstant.VERSION).
+
+To save `World`s with the `f_serde` feature, mark the `Component` `enum` with `#serde`
+after its attributes. It then implements
+[`SerdeComponents`](../trait.SerdeComponents.html), so every component's type
+must implement `Serialize` and `DeserializeOwned`. The marker does nothing without `f_serde`.
+
+The `Component` `enum` also implements [`MapEntities`](../trait.MapEntities.html),
+for the components whose types implement it.
+
 The contents of this module were generated by the following code:
 ```
 eks::component! {
     /// A component generated by the `component!` macro
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #serde
     ExampleComponent {
 
         #unit /// A simple boolean component
@@ -29,6 +43,7 @@ eks::component! {
 
         #unit /// A simple flag component
         #variant /// The `Flag` variant
+        #storage(sparse)
         Flag: ()
     }
 }
@@ -42,6 +57,7 @@ mod eks {
 eks::component! {
     /// An component generated by the `component!` macro
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #serde
     ExampleComponent {
 
         #unit /// A simple boolean component
@@ -54,6 +70,7 @@ eks::component! {
 
         #unit /// A simple flag component
         #variant /// The `Flag` variant
+        #storage(sparse)
         Flag: ()
     }
 }
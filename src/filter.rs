@@ -0,0 +1,194 @@
+use std::marker::PhantomData;
+
+use crate::{ColumnRef, Component, Relation, RelationTable, Row, Storage, Table};
+
+/**
+Trait for conditions that a query can be filtered by
+
+This is implemented for `Added`, `Changed`, `Without` and `Related`, and for tuples of up to 12 `Filter`s,
+which match if all of their members do. `()` matches every `Entity`.
+
+Like `ComponentSet`, matching is done in two steps so that a `World`
+can rule out whole archetypes before looking at any `Entity`s.
+*/
+pub trait Filter<C> {
+    /// Where the components the filter looks at are in a `Table`
+    type Columns<'a>: Copy + Send + Sync
+    where
+        C: 'a;
+    /// Get the names of the components the filter reads
+    fn names() -> Vec<&'static str>;
+    /// Find where the components are in a `Table`
+    ///
+    /// Returns `None` if no `Entity` in the table can match.
+    fn columns(table: Table<'_, C>) -> Option<Self::Columns<'_>>;
+    /// Check if an `Entity` matches, given the tick the querying system last ran at
+    fn matches<'a>(row: Row<'a, C>, columns: Self::Columns<'a>, last_run: u64) -> bool;
+}
+
+/**
+Filter for `Entity`s whose component was added since the system last ran
+
+Usable from `map!`, `map_mut!` and `tags!` after a `;`, i.e. `map!(Position; Added<Position> in world)`
+*/
+pub struct Added<T>(PhantomData<T>);
+
+/**
+Filter for `Entity`s whose component was obtained mutably since the system last ran
+
+A component counts as changed when it is obtained through `EntityMut::get_mut`,
+`EntityRefMut::get_mut`, `IndexMut` or a mutable query, whether or not its value
+is actually modified.
+Adding a component also counts as changing it.
+
+Usable from `map!`, `map_mut!` and `tags!` after a `;`, i.e. `map!(Position; Changed<Position> in world)`
+*/
+pub struct Changed<T>(PhantomData<T>);
+
+/**
+Filter for `Entity`s that don't have a component
+
+Usable from `map!`, `map_mut!` and `tags!` after a `;` as `!C`, i.e. `map!(Position; !Frozen in world)`
+*/
+pub struct Without<T>(PhantomData<T>);
+
+/**
+Filter for `Entity`s that are the source of at least one `Relation` `R`
+
+Relations are not components, so this is the only way to query by them.
+It matches every source of an `R`, whatever its target is: filters are types,
+so they can't select the sources of a specific target. Get those with `World::sources`.
+Usable from `map!`, `map_mut!` and `tags!` after a `;`, i.e. `map!(Position; Related<Targeting> in world)`
+*/
+pub struct Related<R>(PhantomData<R>);
+
+impl<T> Filter<T::Enum> for Added<T>
+where
+    T: Component,
+{
+    type Columns<'a>
+        = ColumnRef<'a, T>
+    where
+        T::Enum: 'a;
+    fn names() -> Vec<&'static str> {
+        vec![T::AS_STR]
+    }
+    fn columns(table: Table<'_, T::Enum>) -> Option<ColumnRef<'_, T>> {
+        table.column::<T>()
+    }
+    fn matches<'a>(row: Row<'a, T::Enum>, column: ColumnRef<'a, T>, last_run: u64) -> bool {
+        column.ticks(row).is_some_and(|(added, _)| added > last_run)
+    }
+}
+
+impl<T> Filter<T::Enum> for Changed<T>
+where
+    T: Component,
+{
+    type Columns<'a>
+        = ColumnRef<'a, T>
+    where
+        T::Enum: 'a;
+    fn names() -> Vec<&'static str> {
+        vec![T::AS_STR]
+    }
+    fn columns(table: Table<'_, T::Enum>) -> Option<ColumnRef<'_, T>> {
+        table.column::<T>()
+    }
+    fn matches<'a>(row: Row<'a, T::Enum>, column: ColumnRef<'a, T>, last_run: u64) -> bool {
+        column
+            .ticks(row)
+            .is_some_and(|(_, changed)| changed > last_run)
+    }
+}
+
+impl<T> Filter<T::Enum> for Without<T>
+where
+    T: Component,
+{
+    /// Where the component is if it is a sparse component
+    type Columns<'a>
+        = Option<ColumnRef<'a, T>>
+    where
+        T::Enum: 'a;
+    fn names() -> Vec<&'static str> {
+        Vec::new()
+    }
+    fn columns(table: Table<'_, T::Enum>) -> Option<Option<ColumnRef<'_, T>>> {
+        match table.column::<T>() {
+            // Every `Entity` in the table has the component
+            Some(_) if T::STORAGE == Storage::Table => None,
+            column => Some(column),
+        }
+    }
+    fn matches<'a>(row: Row<'a, T::Enum>, column: Option<ColumnRef<'a, T>>, _: u64) -> bool {
+        column.is_none_or(|column| !column.contains(row))
+    }
+}
+
+impl<C, R> Filter<C> for Related<R>
+where
+    R: Relation,
+{
+    /// The pairs of the relation, if any were ever added
+    type Columns<'a>
+        = Option<&'a RelationTable>
+    where
+        C: 'a;
+    /// Relations are not components, and are only changed through a mutable `World`
+    fn names() -> Vec<&'static str> {
+        Vec::new()
+    }
+    fn columns(table: Table<'_, C>) -> Option<Option<&RelationTable>> {
+        Some(table.relation::<R>())
+    }
+    fn matches<'a>(row: Row<'a, C>, pairs: Option<&'a RelationTable>, _: u64) -> bool {
+        match (pairs, row.id()) {
+            (Some(pairs), Some(id)) => pairs.has_targets(id),
+            _ => false,
+        }
+    }
+}
+
+macro_rules! filter_tuple {
+    ($($t:ident),*) => {
+        impl<C, $($t),*> Filter<C> for ($($t,)*)
+        where
+            $($t: Filter<C>,)*
+        {
+            type Columns<'a>
+                = ($($t::Columns<'a>,)*)
+            where
+                C: 'a;
+            #[allow(unused_mut)]
+            fn names() -> Vec<&'static str> {
+                let mut names = Vec::new();
+                $(names.extend($t::names());)*
+                names
+            }
+            #[allow(unused_variables)]
+            fn columns(table: Table<'_, C>) -> Option<Self::Columns<'_>> {
+                Some(($($t::columns(table)?,)*))
+            }
+            #[allow(non_snake_case, unused_variables)]
+            fn matches<'a>(row: Row<'a, C>, columns: Self::Columns<'a>, last_run: u64) -> bool {
+                let ($($t,)*) = columns;
+                $($t::matches(row, $t, last_run) &&)* true
+            }
+        }
+    };
+}
+
+filter_tuple!();
+filter_tuple!(A);
+filter_tuple!(A, B);
+filter_tuple!(A, B, D);
+filter_tuple!(A, B, D, E);
+filter_tuple!(A, B, D, E, F);
+filter_tuple!(A, B, D, E, F, G);
+filter_tuple!(A, B, D, E, F, G, H);
+filter_tuple!(A, B, D, E, F, G, H, I);
+filter_tuple!(A, B, D, E, F, G, H, I, J);
+filter_tuple!(A, B, D, E, F, G, H, I, J, K);
+filter_tuple!(A, B, D, E, F, G, H, I, J, K, L);
+filter_tuple!(A, B, D, E, F, G, H, I, J, K, L, M);
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use crate::Id;
+
+/// The parent and children of the `Entity`s in a `World` that have them
+#[derive(Default)]
+pub(crate) struct Hierarchy {
+    parents: HashMap<Id, Id>,
+    /// The children of each parent in the order they were added
+    children: HashMap<Id, Vec<Id>>,
+}
+
+impl Hierarchy {
+    pub(crate) fn parent(&self, id: Id) -> Option<Id> {
+        self.parents.get(&id).copied()
+    }
+    pub(crate) fn children(&self, id: Id) -> &[Id] {
+        self.children.get(&id).map_or(&[], Vec::as_slice)
+    }
+    /// Check if `ancestor` is `id` or one of its ancestors
+    pub(crate) fn is_ancestor(&self, ancestor: Id, id: Id) -> bool {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.parent(id);
+        }
+        false
+    }
+    /// Make `parent` the parent of `child` and return the old parent
+    ///
+    /// The caller must make sure that this doesn't create a cycle.
+    pub(crate) fn set_parent(&mut self, child: Id, parent: Id) -> Option<Id> {
+        let old = self.remove_parent(child);
+        self.parents.insert(child, parent);
+        self.children.entry(parent).or_default().push(child);
+        old
+    }
+    pub(crate) fn remove_parent(&mut self, child: Id) -> Option<Id> {
+        let parent = self.parents.remove(&child)?;
+        if let Some(siblings) = self.children.get_mut(&parent) {
+            siblings.retain(|&id| id != child);
+            if siblings.is_empty() {
+                self.children.remove(&parent);
+            }
+        }
+        Some(parent)
+    }
+    /// Forget an `Entity` that was removed from the `World`,
+    /// leaving its children without a parent
+    pub(crate) fn remove(&mut self, id: Id) {
+        self.remove_parent(id);
+        for child in self.children.remove(&id).unwrap_or_default() {
+            self.parents.remove(&child);
+        }
+    }
+}
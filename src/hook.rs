@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use crate::{Commands, Id, Row};
+
+/// When a component hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum HookKind {
+    /// The component is added to an `Entity` that didn't have it
+    Add,
+    /// A value of the component is added to an `Entity`, whether or not it had one
+    Insert,
+    /// The component is removed from an `Entity`
+    Remove,
+}
+
+/// A hook that finds the component's value in the `Row` of the `Entity`
+pub(crate) type Hook<C> = Box<dyn Fn(Id, Row<'_, C>, &mut Commands<C>) + Send + Sync>;
+
+/// The component hooks registered with a `World`
+pub(crate) struct Hooks<C> {
+    hooks: HashMap<(&'static str, HookKind), Vec<Hook<C>>>,
+}
+
+impl<C> Default for Hooks<C> {
+    fn default() -> Self {
+        Hooks {
+            hooks: HashMap::new(),
+        }
+    }
+}
+
+impl<C> Hooks<C> {
+    pub(crate) fn insert(&mut self, name: &'static str, kind: HookKind, hook: Hook<C>) {
+        self.hooks.entry((name, kind)).or_default().push(hook);
+    }
+    pub(crate) fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+    /// Run the hooks of the given kind for one component of an `Entity`, if it has it
+    pub(crate) fn run(
+        &self,
+        kind: HookKind,
+        name: &'static str,
+        id: Id,
+        row: Row<'_, C>,
+        commands: &mut Commands<C>,
+    ) {
+        if let Some(hooks) = self.hooks.get(&(name, kind)) {
+            for hook in hooks {
+                hook(id, row, commands);
+            }
+        }
+    }
+    /// Run the hooks of the given kind for every component of an `Entity`
+    pub(crate) fn run_all(
+        &self,
+        kind: HookKind,
+        id: Id,
+        row: Row<'_, C>,
+        commands: &mut Commands<C>,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        for name in row.names() {
+            self.run(kind, name, id, row, commands);
+        }
+    }
+}
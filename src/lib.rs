@@ -4,7 +4,10 @@
 `eks` is an entity-component system crate with a focus on simplicity and ergonomics.
 
 Features:
-    * `f_rayon` Use rayon parallel iterators
+    * `f_rayon` Use rayon parallel iterators and run non-conflicting systems in parallel
+    * `f_serde` Serialize and deserialize `Id`s, `Entity`s and `World`s with serde, for component enums marked `#serde`
+    * `f_snapshot` Save `World`s to compact binary snapshots that can be migrated between schema versions. Enables `f_serde`.
+    * `f_uuid` Give each `Entity` a stable uuid
 
 # Example
 ```
@@ -43,48 +46,200 @@ assert_eq!(1, tags!(Special in world).count())
 ```
 */
 
+mod access;
+mod commands;
+mod event;
 pub mod example_component;
+mod filter;
+mod hierarchy;
+mod hook;
 mod map;
+mod map_entities;
+mod observer;
+mod query;
+mod relation;
+mod resource;
+mod scene;
+mod schedule;
+#[cfg(feature = "f_serde")]
+mod serialization;
+#[cfg(feature = "f_snapshot")]
+mod snapshot;
+mod storage;
+mod world;
 
-use std::{
-    collections::HashMap,
-    fmt,
-    ops::{Index, IndexMut},
-};
+use std::{cell::UnsafeCell, fmt};
 
-#[cfg(feature = "f_rayon")]
-use rayon::prelude::*;
+#[cfg(feature = "f_uuid")]
 use uuid::Uuid;
 
-pub use crate::map::*;
+#[cfg(feature = "f_serde")]
+pub use crate::serialization::*;
+#[cfg(feature = "f_snapshot")]
+pub use crate::snapshot::*;
+pub use crate::{
+    access::*, commands::*, event::*, filter::*, map_entities::*, query::*, relation::*, scene::*,
+    schedule::*, storage::*, world::*,
+};
+
+#[cfg(feature = "f_serde")]
+#[doc(hidden)]
+pub use serde;
+
+#[cfg(not(feature = "f_serde"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! serde_components {
+    ($($tt:tt)*) => {};
+}
 
 /**
 Trait for components
 
 You do not need to impliment this trait manually.
 The `component!` macro will do it for you.
+
+# Safety
+
+`Type` must be the type of the variant of `Enum` named `AS_STR`, which `new` wraps
+values in, and no other component of `Enum` may have the same `AS_STR`.
+A `World` stores the values of each component apart from the enum,
+and relies on this to know that they are `Send` and `Sync` when the enum is.
 */
-pub trait Component {
+pub unsafe trait Component: 'static {
     /// The component's type
-    type Type;
+    type Type: 'static;
     /// The component's associated enum
     type Enum;
     /// Create a new component enum from the value
     fn new(val: Self::Type) -> Self::Enum;
     #[doc(hidden)]
     const AS_STR: &'static str;
-    /// Try to get a reference to this component from an `Entity`
-    #[doc(hidden)]
-    fn try_entity(entity: &Entity<Self::Enum>) -> Option<&Self::Type>;
-    /// Try to get a mutable reference to this component from an `Entity`
-    #[doc(hidden)]
-    fn try_entity_mut(entity: &Entity<Self::Enum>) -> Option<&mut Self::Type>;
+    /// How the component is stored in a `World`
+    const STORAGE: Storage = Storage::Table;
+    /**
+    The version of the component's type, which snapshots record
+
+    Set with `#version(n)` in the `component!` macro. Bump it when the type changes
+    so that old values can't be decoded anymore, and migrate old snapshots with a `Schema`.
+    */
+    const VERSION: u32 = 0;
     #[doc(hidden)]
     fn enum_as_val(enm: &Self::Enum) -> &Self::Type;
     #[doc(hidden)]
     fn enum_as_val_mut(enm: &mut Self::Enum) -> &mut Self::Type;
     #[doc(hidden)]
     fn enum_to_val(enm: Self::Enum) -> Self::Type;
+    /// Call `f` with every `Id` in a value, if the type implements `MapEntities`
+    #[doc(hidden)]
+    fn for_each_entity(val: &Self::Type, f: &mut dyn FnMut(Id));
+    /// Replace every `Id` in a value, if the type implements `MapEntities`
+    #[doc(hidden)]
+    fn map_entities(val: &mut Self::Type, map: &mut dyn FnMut(Id) -> Id);
+    /// Clone a value, or return `None` if the type does not implement `Clone`
+    #[doc(hidden)]
+    fn clone_val(val: &Self::Type) -> Option<Self::Type>;
+}
+
+/**
+Lets the `component!` macro use the `MapEntities` and `Clone` impls of a component's type
+if it has them, and fall back to doing nothing otherwise
+
+`(&&Specialize::<T>(PhantomData)).for_each_entity(..)` picks the impl for `&Specialize<T>`
+when `T` implements the trait, and falls back to the one for `Specialize<T>`.
+*/
+#[doc(hidden)]
+pub struct Specialize<T>(pub std::marker::PhantomData<T>);
+
+#[doc(hidden)]
+pub trait SpecializeMapEntities<T> {
+    fn for_each_entity(&self, val: &T, f: &mut dyn FnMut(Id));
+    fn map_entities(&self, val: &mut T, map: &mut dyn FnMut(Id) -> Id);
+}
+
+impl<T> SpecializeMapEntities<T> for &Specialize<T>
+where
+    T: MapEntities,
+{
+    fn for_each_entity(&self, val: &T, f: &mut dyn FnMut(Id)) {
+        val.for_each_entity(f)
+    }
+    fn map_entities(&self, val: &mut T, map: &mut dyn FnMut(Id) -> Id) {
+        val.map_entities(map)
+    }
+}
+
+#[doc(hidden)]
+pub trait SpecializeNoEntities<T> {
+    fn for_each_entity(&self, val: &T, f: &mut dyn FnMut(Id));
+    fn map_entities(&self, val: &mut T, map: &mut dyn FnMut(Id) -> Id);
+}
+
+impl<T> SpecializeNoEntities<T> for Specialize<T> {
+    fn for_each_entity(&self, _: &T, _: &mut dyn FnMut(Id)) {}
+    fn map_entities(&self, _: &mut T, _: &mut dyn FnMut(Id) -> Id) {}
+}
+
+#[doc(hidden)]
+pub trait SpecializeClone<T> {
+    fn clone_val(&self, val: &T) -> Option<T>;
+}
+
+impl<T> SpecializeClone<T> for &Specialize<T>
+where
+    T: Clone,
+{
+    fn clone_val(&self, val: &T) -> Option<T> {
+        Some(val.clone())
+    }
+}
+
+#[doc(hidden)]
+pub trait SpecializeNoClone<T> {
+    fn clone_val(&self, val: &T) -> Option<T>;
+}
+
+impl<T> SpecializeNoClone<T> for Specialize<T> {
+    fn clone_val(&self, _: &T) -> Option<T> {
+        None
+    }
+}
+
+/**
+How a `Component`'s values are stored in a `World`
+
+Set with `#storage(table)` or `#storage(sparse)` in the `component!` macro.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Storage {
+    /**
+    The component is stored together with the other components
+    of `Entity`s that have the same set of components
+
+    This is the default. It is the fastest to iterate over,
+    but adding or removing the component moves the `Entity`
+    to a different archetype.
+    */
+    Table,
+    /**
+    The component is stored apart from the `Entity`'s archetype,
+    in a sparse set keyed by the `Entity`'s index
+
+    Adding or removing the component is cheap, so this is
+    a good fit for components that come and go every frame.
+    */
+    Sparse,
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! storage {
+    (table) => {
+        eks::Storage::Table
+    };
+    (sparse) => {
+        eks::Storage::Sparse
+    };
 }
 
 /**
@@ -94,15 +249,22 @@ For a usage example, check out [the component example module](example_component/
 */
 #[macro_export]
 macro_rules! component {
-    ($(#[$top_attr:meta])* $name:ident { $($(#unit #[$unit_attr:meta])* $(#variant #[$variant_attr:meta])* $id:ident: $ty:ty),* $(,)* }) => {
+    (@serde [] $name:ident { $($id:ident),* }) => {};
+    (@serde [serde] $name:ident { $($id:ident),* }) => {
+        eks::serde_components!{ $name { $($id),* } }
+    };
+    (@impl [$($serde:ident)?] $(#[$top_attr:meta])* $name:ident { $($(#unit #[$unit_attr:meta])* $(#variant #[$variant_attr:meta])* $(#storage($storage:ident))? $(#version($version:literal))? $id:ident: $ty:ty),* $(,)* }) => {
         $(
             #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
             $(#[$unit_attr])*
             pub struct $id {}
-            impl eks::Component for $id {
+            unsafe impl eks::Component for $id {
                 const AS_STR: &'static str = stringify!($id);
+                $(const STORAGE: eks::Storage = eks::storage!($storage);)?
+                $(const VERSION: u32 = $version;)?
                 type Type = $ty;
                 type Enum = $name;
+                #[allow(irrefutable_let_patterns)]
                 fn enum_as_val(enm: &Self::Enum) -> &$ty {
                     if let $name::$id(val) = enm {
                         val
@@ -110,6 +272,7 @@ macro_rules! component {
                         panic!(concat!("Component is not ", stringify!($id)))
                     }
                 }
+                #[allow(irrefutable_let_patterns)]
                 fn enum_as_val_mut(enm: &mut Self::Enum) -> &mut $ty {
                     if let $name::$id(val) = enm {
                         val
@@ -117,6 +280,7 @@ macro_rules! component {
                         panic!(concat!("Component is not ", stringify!($id)))
                     }
                 }
+                #[allow(irrefutable_let_patterns)]
                 fn enum_to_val(enm: Self::Enum) -> $ty {
                     if let $name::$id(val) = enm {
                         val
@@ -129,22 +293,20 @@ macro_rules! component {
                 fn new(val: $ty) -> $name {
                     $name::$id(val)
                 }
-                /// Try to get a reference to this component from an `Entity`
-                #[doc(hidden)]
-                fn try_entity(entity: &eks::Entity<$name>) -> Option<&$ty> {
-                    entity.get::<$id>()
+                #[allow(unused_imports)]
+                fn for_each_entity(val: &$ty, f: &mut dyn FnMut(eks::Id)) {
+                    use eks::{SpecializeMapEntities, SpecializeNoEntities};
+                    (&&eks::Specialize::<$ty>(std::marker::PhantomData)).for_each_entity(val, f)
                 }
-                /// Try to get a mutable reference to this component from an `Entity`
-                #[doc(hidden)]
-                fn try_entity_mut(entity: &eks::Entity<$name>) -> Option<&mut $ty> {
-                    unsafe {
-                        (entity
-                            as *const eks::Entity<$name>
-                            as *mut eks::Entity<$name>
-                        ).as_mut()
-                    }
-                        .unwrap()
-                        .get_mut::<$id>()
+                #[allow(unused_imports)]
+                fn map_entities(val: &mut $ty, map: &mut dyn FnMut(eks::Id) -> eks::Id) {
+                    use eks::{SpecializeMapEntities, SpecializeNoEntities};
+                    (&&eks::Specialize::<$ty>(std::marker::PhantomData)).map_entities(val, map)
+                }
+                #[allow(unused_imports)]
+                fn clone_val(val: &$ty) -> Option<$ty> {
+                    use eks::{SpecializeClone, SpecializeNoClone};
+                    (&&eks::Specialize::<$ty>(std::marker::PhantomData)).clone_val(val)
                 }
             }
             impl std::fmt::Display for $id {
@@ -169,6 +331,52 @@ macro_rules! component {
                         .unwrap_or_else(|| panic!("Unable to find component {:?}", stringify!($id)))
                 }
             }
+            impl std::ops::Index<$id> for eks::EntityView<$name> {
+                type Output = $ty;
+                fn index(&self, _: $id) -> &Self::Output {
+                    self.get::<$id>()
+                        .unwrap_or_else(|| panic!("Unable to find component {:?}", stringify!($id)))
+                }
+            }
+            impl std::ops::IndexMut<$id> for eks::EntityView<$name> {
+                fn index_mut(&mut self, _: $id) -> &mut Self::Output {
+                    self.get_mut::<$id>()
+                        .unwrap_or_else(|| panic!("Unable to find component {:?}", stringify!($id)))
+                }
+            }
+            impl std::ops::Index<$id> for eks::EntityRef<'_, $name> {
+                type Output = $ty;
+                fn index(&self, _: $id) -> &Self::Output {
+                    self.get::<$id>()
+                        .unwrap_or_else(|| panic!("Unable to find component {:?}", stringify!($id)))
+                }
+            }
+            impl std::ops::Index<$id> for eks::EntityRefMut<'_, $name> {
+                type Output = $ty;
+                fn index(&self, _: $id) -> &Self::Output {
+                    self.get::<$id>()
+                        .unwrap_or_else(|| panic!("Unable to find component {:?}", stringify!($id)))
+                }
+            }
+            impl std::ops::IndexMut<$id> for eks::EntityRefMut<'_, $name> {
+                fn index_mut(&mut self, _: $id) -> &mut Self::Output {
+                    self.get_mut::<$id>()
+                        .unwrap_or_else(|| panic!("Unable to find component {:?}", stringify!($id)))
+                }
+            }
+            impl std::ops::Index<$id> for eks::EntityMut<'_, $name> {
+                type Output = $ty;
+                fn index(&self, _: $id) -> &Self::Output {
+                    self.get::<$id>()
+                        .unwrap_or_else(|| panic!("Unable to find component {:?}", stringify!($id)))
+                }
+            }
+            impl std::ops::IndexMut<$id> for eks::EntityMut<'_, $name> {
+                fn index_mut(&mut self, _: $id) -> &mut Self::Output {
+                    self.get_mut::<$id>()
+                        .unwrap_or_else(|| panic!("Unable to find component {:?}", stringify!($id)))
+                }
+            }
         )*
         $(#[$top_attr])*
         pub enum $name {
@@ -182,53 +390,193 @@ macro_rules! component {
                 }
             }
         }
+
+        impl eks::MapEntities for $name {
+            fn for_each_entity(&self, f: &mut dyn FnMut(eks::Id)) {
+                match self {
+                    $($name::$id(val) => <$id as eks::Component>::for_each_entity(val, f)),*
+                }
+            }
+            fn map_entities(&mut self, map: &mut dyn FnMut(eks::Id) -> eks::Id) {
+                match self {
+                    $($name::$id(val) => <$id as eks::Component>::map_entities(val, map)),*
+                }
+            }
+        }
+
+        eks::component!{ @serde [$($serde)?] $name { $($id),* } }
+    };
+    (@impl [$($serde:ident)?] $(#[$top_attr:meta])* $($(#unit #[$unit_attr:meta])* $(#variant #[$variant_attr:meta])* $(#storage($storage:ident))? $(#version($version:literal))? $id:ident: $ty:ty),* $(,)*) => {
+        eks::component!{ @impl [$($serde)?] $(#[$top_attr])* Comp { $( $(#unit #[$unit_attr])* $(#variant #[$variant_attr])* $(#storage($storage))? $(#version($version))? $id: $ty),* } }
+    };
+    (@ $($rest:tt)*) => {
+        compile_error!("Unable to parse the components");
     };
-    ($(#[$top_attr:meta])* $($(#unit #[$unit_attr:meta])* $(#variant #[$variant_attr:meta])* $id:ident: $ty:ty),* $(,)*) => {
-        eks::component!{ $(#[$top_attr])* Comp { $( $(#unit #[$unit_attr])* $(#variant #[$variant_attr])* $id: $ty),* } }
+    ($(#[$top_attr:meta])* #serde $($rest:tt)*) => {
+        eks::component!{ @impl [serde] $(#[$top_attr])* $($rest)* }
+    };
+    ($($rest:tt)*) => {
+        eks::component!{ @impl [] $($rest)* }
     };
 }
 
-/// An `Entity` id
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Id(Uuid);
+/**
+An `Entity` id
+
+Ids are handed out by a `World` when an `Entity` is inserted.
+An id is made up of an index into the `World`'s storage and a
+generation that is incremented every time the index is reused,
+so ids of removed `Entity`s are never mistaken for new ones.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id {
+    index: u32,
+    generation: u32,
+}
 
 impl Id {
-    fn new() -> Id {
-        Id(Uuid::new_v4())
+    /// Get the index part of the id
+    pub fn index(self) -> u32 {
+        self.index
+    }
+    /// Get the generation part of the id
+    pub fn generation(self) -> u32 {
+        self.generation
     }
 }
 
 impl fmt::Debug for Id {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        <Uuid as fmt::Debug>::fmt(&self.0, f)
+        write!(f, "{}v{}", self.index, self.generation)
     }
 }
 
 impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        <Uuid as fmt::Display>::fmt(&self.0, f)
-    }
-}
-
-impl Default for Id {
-    fn default() -> Self {
-        Id::new()
+        write!(f, "{}v{}", self.index, self.generation)
     }
 }
 
 /**
 An entity in the ECS
+
+This is an `Entity` that is not in a `World`, which owns its components.
+Once it is inserted, its components are stored in the `World`'s archetypes
+and it is accessed through an `EntityRef`.
 */
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Entity<C> {
-    /// The id of the `Entity`
-    id: Id,
-    /// A map of formatted component names to indices in
-    /// the `components`
-    #[doc(hidden)]
-    pub components: HashMap<&'static str, C>,
+    /// A stable identifier that is kept across `World`s
+    #[cfg(feature = "f_uuid")]
+    uuid: Uuid,
+    /// The `Entity`'s table components, sorted by name
+    components: Vec<(&'static str, Slot<C>)>,
+    /// The `Entity`'s sparse components, sorted by name
+    sparse: Vec<(&'static str, Slot<C>)>,
+}
+
+/**
+A component value stored in an `Entity` or in a sparse set of a `World`
+
+The value is kept in an `UnsafeCell` so that a query can hand out
+mutable references to several different slots of the same `Entity`.
+A mutable reference may only be created through `Slot::get_unchecked_mut`
+by code that has exclusive access to the `Entity`.
+
+The slot also keeps the ticks at which the component was added to a `World`
+and last obtained mutably there, which are used by `Added` and `Changed`,
+and how to create the column that a `World` stores the component's values in.
+*/
+pub(crate) struct Slot<C> {
+    value: UnsafeCell<C>,
+    added: u64,
+    changed: UnsafeCell<u64>,
+    new_column: fn() -> Box<dyn AnyColumn<C>>,
+}
+
+// Safety: the value is only mutated through `&mut Slot` or by
+// `get_unchecked_mut`, whose callers have exclusive access to the
+// slot. That access may come from another thread when systems run
+// in parallel, so `C` has to be `Send` as well as `Sync`.
+unsafe impl<C: Send + Sync> Sync for Slot<C> {}
+
+impl<C> Slot<C> {
+    pub(crate) fn new<T>(value: T::Type) -> Self
+    where
+        T: Component<Enum = C>,
+    {
+        Slot {
+            value: UnsafeCell::new(T::new(value)),
+            added: 0,
+            changed: UnsafeCell::new(0),
+            new_column: new_column::<T>,
+        }
+    }
+    pub(crate) fn get(&self) -> &C {
+        // Safety: see `get_unchecked_mut`
+        unsafe { &*self.value.get() }
+    }
+    /// Get a mutable reference to the value and mark it as changed at `tick`
+    pub(crate) fn get_mut(&mut self, tick: u64) -> &mut C {
+        *self.changed.get_mut() = tick;
+        self.value.get_mut()
+    }
+    /// Get a mutable reference to the value through a shared reference
+    /// and mark it as changed at `tick`
+    ///
+    /// # Safety
+    ///
+    /// The caller must have exclusive access to this `Slot`'s value
+    /// and must not create more than one reference to it.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_unchecked_mut(&self, tick: u64) -> &mut C {
+        *self.changed.get() = tick;
+        &mut *self.value.get()
+    }
+    /// Get the tick at which the component was added
+    pub(crate) fn added(&self) -> u64 {
+        self.added
+    }
+    /// Get the tick at which the component was last obtained mutably
+    pub(crate) fn changed(&self) -> u64 {
+        // Safety: see `get_unchecked_mut`
+        unsafe { *self.changed.get() }
+    }
+    /// Mark the component as added and changed at `tick`
+    pub(crate) fn stamp(&mut self, tick: u64) {
+        self.added = tick;
+        *self.changed.get_mut() = tick;
+    }
+    pub(crate) fn into_inner(self) -> C {
+        self.value.into_inner()
+    }
+}
+
+impl<C: Clone> Clone for Slot<C> {
+    fn clone(&self) -> Self {
+        Slot {
+            value: UnsafeCell::new(self.get().clone()),
+            added: self.added,
+            changed: UnsafeCell::new(self.changed()),
+            new_column: self.new_column,
+        }
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for Slot<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.get().fmt(f)
+    }
 }
 
+impl<C: PartialEq> PartialEq for Slot<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl<C: Eq> Eq for Slot<C> {}
+
 impl<C> Default for Entity<C> {
     fn default() -> Self {
         Entity::new()
@@ -239,51 +587,121 @@ impl<C> Entity<C> {
     /// Create a new `Entity`
     pub fn new() -> Entity<C> {
         Entity {
-            id: Id::new(),
-            components: HashMap::new(),
+            #[cfg(feature = "f_uuid")]
+            uuid: Uuid::new_v4(),
+            components: Vec::new(),
+            sparse: Vec::new(),
         }
     }
-    /// Gets the `Entity`'s id
-    pub fn id(&self) -> Id {
-        self.id
+    /// Gets the `Entity`'s uuid
+    ///
+    /// Unlike its `Id`, an `Entity`'s uuid is generated when it is created
+    /// and does not change when it is moved between `World`s.
+    #[cfg(feature = "f_uuid")]
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+    /// Get the slot of a component
+    pub(crate) fn slot(&self, name: &str, storage: Storage) -> Option<&Slot<C>> {
+        let slots = match storage {
+            Storage::Table => &self.components,
+            Storage::Sparse => &self.sparse,
+        };
+        let i = find_slot(slots, name).ok()?;
+        Some(&slots[i].1)
+    }
+    /// Get the names of the `Entity`'s table components in sorted order
+    pub(crate) fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.components.iter().map(|(name, _)| *name)
+    }
+    /// Get the names of the `Entity`'s sparse components
+    pub(crate) fn sparse_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.sparse.iter().map(|(name, _)| *name)
+    }
+    fn slots_mut(&mut self, storage: Storage) -> &mut Vec<(&'static str, Slot<C>)> {
+        match storage {
+            Storage::Table => &mut self.components,
+            Storage::Sparse => &mut self.sparse,
+        }
     }
     /// Get an optional reference to a component's value
     pub fn get<T>(&self) -> Option<&T::Type>
     where
         T: Component<Enum = C>,
     {
-        if let Some(component) = self.components.get(T::AS_STR) {
-            Some(T::enum_as_val(component))
-        } else {
-            None
-        }
+        let slot = self.slot(T::AS_STR, T::STORAGE)?;
+        Some(T::enum_as_val(slot.get()))
     }
     /// Get an optional mutable reference to a component's value
     pub fn get_mut<T>(&mut self) -> Option<&mut T::Type>
     where
         T: Component<Enum = C>,
     {
-        if let Some(component) = self.components.get_mut(T::AS_STR) {
-            Some(T::enum_as_val_mut(component))
-        } else {
-            None
-        }
+        let slots = self.slots_mut(T::STORAGE);
+        let i = find_slot(slots, T::AS_STR).ok()?;
+        Some(T::enum_as_val_mut(slots[i].1.get_mut(0)))
+    }
+    /**
+    Get optional references to several components' values at once
+
+    `T` is either a single `Component`, an `Option` of one or a tuple of them.
+    Returns `None` if the `Entity` is missing any of the components
+    that are not wrapped in an `Option`.
+    */
+    pub fn get_many<'a, T>(&'a self) -> Option<T::Ref>
+    where
+        T: ComponentSet<'a, C>,
+    {
+        AsRow::fetch::<T>(self)
+    }
+    /**
+    Get optional mutable references to several components' values at once
+
+    `T` is either a single `Component`, an `Option` of one or a tuple of them.
+    Returns `None` if the `Entity` is missing any of the components
+    that are not wrapped in an `Option`.
+
+    Specifying the same component twice fails to compile:
+    ```compile_fail
+    use eks::*;
+    component! { Foo: u8 }
+    let mut entity = entity! { Foo: 1 };
+    entity.get_many_mut::<(Foo, Foo)>();
+    ```
+    */
+    pub fn get_many_mut<'a, T>(&'a mut self) -> Option<T::Mut>
+    where
+        T: ComponentSet<'a, C>,
+    {
+        AsRowMut::fetch_mut::<T>(self)
     }
     /// Check if the `Entity` has the `Component`
     pub fn has<T>(&self) -> bool
     where
         T: Component<Enum = C>,
     {
-        self.get::<T>().is_some()
+        self.slot(T::AS_STR, T::STORAGE).is_some()
     }
     /// Add a `Component` to the `Entity`
     pub fn add<T>(&mut self, value: T::Type) -> Option<T::Type>
     where
         T: Component<Enum = C>,
     {
-        self.components
-            .insert(T::AS_STR, T::new(value))
-            .map(T::enum_to_val)
+        self.add_at::<T>(value, 0)
+    }
+    /// Add a `Component` to the `Entity` and mark it as changed at `tick`,
+    /// and also as added if the `Entity` did not have it
+    pub(crate) fn add_at<T>(&mut self, value: T::Type, tick: u64) -> Option<T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        add_slot::<T>(self.slots_mut(T::STORAGE), value, tick)
+    }
+    /// Mark all of the components as added and changed at `tick`
+    pub(crate) fn stamp(&mut self, tick: u64) {
+        for (_, slot) in self.components.iter_mut().chain(&mut self.sparse) {
+            slot.stamp(tick);
+        }
     }
     /// Add a `Component` to the `Entity`
     pub fn with<T>(mut self, value: T::Type) -> Self
@@ -298,10 +716,48 @@ impl<C> Entity<C> {
     where
         T: Component<Enum = C>,
     {
-        self.components.remove(T::AS_STR).map(T::enum_to_val)
+        remove_slot::<T>(self.slots_mut(T::STORAGE))
     }
 }
 
+/// Binary search for a component's slot by name
+fn find_slot<C>(slots: &[(&'static str, Slot<C>)], name: &str) -> Result<usize, usize> {
+    slots.binary_search_by(|(slot, _)| (*slot).cmp(name))
+}
+
+/// Add a component to sorted slots and mark it as changed at `tick`,
+/// and also as added if it was not there
+fn add_slot<T>(
+    slots: &mut Vec<(&'static str, Slot<T::Enum>)>,
+    value: T::Type,
+    tick: u64,
+) -> Option<T::Type>
+where
+    T: Component,
+{
+    match find_slot(slots, T::AS_STR) {
+        Ok(i) => Some(T::enum_to_val(std::mem::replace(
+            slots[i].1.get_mut(tick),
+            T::new(value),
+        ))),
+        Err(i) => {
+            let mut slot = Slot::new::<T>(value);
+            slot.stamp(tick);
+            slots.insert(i, (T::AS_STR, slot));
+            None
+        }
+    }
+}
+
+/// Remove a component from sorted slots
+fn remove_slot<T>(slots: &mut Vec<(&'static str, Slot<T::Enum>)>) -> Option<T::Type>
+where
+    T: Component,
+{
+    let i = find_slot(slots, T::AS_STR).ok()?;
+    Some(T::enum_to_val(slots.remove(i).1.into_inner()))
+}
+
 /**
 Creates an `Entity` with `struct`-like syntax
 
@@ -333,128 +789,39 @@ macro_rules! entity {
     };
 }
 
-/// The world of the ECS
-pub struct World<C> {
-    entities: HashMap<Id, Entity<C>>,
-}
-
-impl<C> Default for World<C> {
-    fn default() -> Self {
-        World::new()
-    }
-}
-
-impl<C> World<C> {
-    /// Create a new `World`
-    pub fn new() -> World<C> {
-        World {
-            entities: HashMap::new(),
-        }
-    }
-    /// Add an `Entity` to the `World`
-    pub fn insert(&mut self, entity: Entity<C>) -> Id {
-        let id = entity.id();
-        self.entities.insert(id, entity);
-        id
-    }
-    /// Removes the `Entity` with the given id
-    pub fn remove(&mut self, id: Id) -> Option<Entity<C>> {
-        self.entities.remove(&id)
-    }
-    /// Iterates through all `Entities` in the `World`
-    pub fn iter(&self) -> std::collections::hash_map::Values<Id, Entity<C>> {
-        self.entities.values()
-    }
-    /// Mutable iterates through all `Entities` in the `World`
-    pub fn iter_mut(&mut self) -> std::collections::hash_map::ValuesMut<Id, Entity<C>> {
-        self.entities.values_mut()
-    }
-    /// Get a reference to the `Entity` with the given `Id`
-    pub fn get(&self, id: Id) -> Option<&Entity<C>> {
-        self.entities.get(&id)
-    }
-    /// Get a mutable reference to the `Entity` with the given `Id`
-    pub fn get_mut(&mut self, id: Id) -> Option<&mut Entity<C>> {
-        self.entities.get_mut(&id)
-    }
-}
-
-impl<C> Index<Id> for World<C> {
-    type Output = Entity<C>;
-    fn index(&self, id: Id) -> &Self::Output {
-        self.get(id)
-            .unwrap_or_else(|| panic!("Unable to find entity with id: {}", id))
-    }
-}
-
-impl<C> IndexMut<Id> for World<C> {
-    fn index_mut(&mut self, id: Id) -> &mut Self::Output {
-        self.get_mut(id)
-            .unwrap_or_else(|| panic!("Unable to find entity with id: {}", id))
-    }
-}
-
-#[cfg(feature = "f_rayon")]
-impl<'a, C> IntoParallelIterator for &'a World<C>
-where
-    C: Sync,
-{
-    type Item = <&'a HashMap<Id, Entity<C>> as IntoIterator>::Item;
-    type Iter = rayon::collections::hash_map::Iter<'a, Id, Entity<C>>;
-    fn into_par_iter(self) -> Self::Iter {
-        self.entities.into_par_iter()
-    }
-}
-
-#[cfg(feature = "f_rayon")]
-impl<'a, C> IntoParallelIterator for &'a mut World<C>
-where
-    C: Send,
-{
-    type Item = <&'a mut HashMap<Id, Entity<C>> as IntoIterator>::Item;
-    type Iter = rayon::collections::hash_map::IterMut<'a, Id, Entity<C>>;
-    fn into_par_iter(self) -> Self::Iter {
-        (&mut self.entities).into_par_iter()
-    }
-}
-
-/// An iterator adapter that converts and `Entity` iterator to
+/// An iterator adapter that converts and `EntityRef` iterator to
 /// an iterator over the `Entity`s' ids
-pub struct Ids<C, I> {
+pub struct Ids<I> {
     iter: I,
-    pd: std::marker::PhantomData<C>,
 }
 
-impl<C, E, I> Iterator for Ids<C, I>
+impl<'a, C, I> Iterator for Ids<I>
 where
-    E: std::borrow::Borrow<Entity<C>>,
-    I: Iterator<Item = E>,
+    C: 'a,
+    I: Iterator<Item = EntityRef<'a, C>>,
 {
     type Item = Id;
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|e| e.borrow().id())
+        self.iter.next().map(|e| e.id())
     }
 }
 
-/// Adds adapter functions for `Entity` iterators
-pub trait EntityIterator<C, E>: Iterator<Item = E> + Sized
+/// Adds adapter functions for `EntityRef` iterators
+pub trait EntityIterator<'a, C>: Iterator<Item = EntityRef<'a, C>> + Sized
 where
-    E: std::borrow::Borrow<Entity<C>>,
+    C: 'a,
 {
-    /// Converts and `Entity` iterator to
+    /// Converts and `EntityRef` iterator to
     /// an iterator over the `Entity`'s ids
-    fn ids(self) -> Ids<C, Self> {
-        Ids {
-            iter: self,
-            pd: std::marker::PhantomData,
-        }
+    fn ids(self) -> Ids<Self> {
+        Ids { iter: self }
     }
 }
 
-impl<C, E, I> EntityIterator<C, E> for I
+impl<'a, C, I> EntityIterator<'a, C> for I
 where
-    I: Iterator<Item = E> + Sized,
-    E: std::borrow::Borrow<Entity<C>>,
+    C: 'a,
+    I: Iterator<Item = EntityRef<'a, C>> + Sized,
 {
 }
 
@@ -481,7 +848,7 @@ mod test {
             Speed: 3,
         });
 
-        for (position, speed) in map_mut_checked!(Position, Speed in world) {
+        for (position, speed) in map_mut!(Position, Speed in world) {
             *position += *speed
         }
 
@@ -489,13 +856,1203 @@ mod test {
         assert_eq!(1, tags!(Speed in world).count());
     }
     #[test]
+    fn get_many_mut() {
+        component! { Foo: u8, Bar: u8, Baz: () }
+        let mut entity = entity! { Foo: 1, Bar: 2 };
+        if let Some((foo, bar)) = entity.get_many_mut::<(Foo, Bar)>() {
+            std::mem::swap(foo, bar);
+        }
+        assert_eq!((2, 1), (entity[Foo], entity[Bar]));
+        assert!(entity.get_many_mut::<(Foo, Baz)>().is_none());
+    }
+    #[test]
+    fn stale_ids() {
+        component! { Foo: u8 }
+        let mut world = World::new();
+        let a = world.insert(entity! { Foo: 1 });
+        assert_eq!(a, world[a].id());
+        assert_eq!(Some(1), world.remove(a).map(|e| e[Foo]));
+        assert!(world.get(a).is_none());
+        assert!(world.remove(a).is_none());
+        let b = world.insert(entity! { Foo: 2 });
+        assert_eq!(a.index(), b.index());
+        assert_ne!(a, b);
+        assert!(!world.contains(a));
+        assert_eq!(2, world[b][Foo]);
+        assert_eq!(1, world.len());
+    }
+    #[test]
+    fn archetypes() {
+        component! { Foo: u8, Bar: u8 }
+        let mut world = World::new();
+        let a = world.insert(entity! { Foo: 1 });
+        let b = world.insert(entity! { Foo: 2 });
+        let c = world.insert(entity! { Foo: 3, Bar: 3 });
+        {
+            let mut entity = world.get_mut(a).unwrap();
+            assert_eq!(None, entity.add::<Bar>(1));
+            entity[Bar] += 1;
+            assert_eq!(2, entity[Bar]);
+        }
+        assert_eq!(2, world[b][Foo]);
+        assert_eq!(2, map!(Foo, Bar in world).count());
+        assert_eq!(Some(3), world.get_mut(c).unwrap().remove::<Bar>());
+        assert_eq!(Some(2), world.remove(b).map(|e| e[Foo]));
+        assert_eq!(3, world[c][Foo]);
+        assert_eq!(vec![&2], map!(Bar in world).collect::<Vec<_>>());
+        assert_eq!(2, tags!(Foo in world).count());
+    }
+    #[test]
+    fn sparse() {
+        component! { Foo: u8, #storage(sparse) Selected: () }
+        let mut world = World::new();
+        let a = world.insert(entity! { Foo: 1, Selected: () });
+        let b = world.insert(entity! { Foo: 2 });
+        let c = world.insert(entity! { Selected: () });
+        assert_eq!(2, tags!(Selected in world).count());
+        assert_eq!(
+            vec![&1],
+            map!(Foo, Selected in world)
+                .map(|(foo, _)| foo)
+                .collect::<Vec<_>>()
+        );
+        world.get_mut(b).unwrap().add::<Selected>(());
+        assert_eq!(Some(()), world.get_mut(a).unwrap().remove::<Selected>());
+        for (foo, _) in map_mut!(Foo, Selected in world) {
+            *foo += 1;
+        }
+        assert_eq!((1, 3), (world[a][Foo], world[b][Foo]));
+        assert!(world.remove(c).unwrap().has::<Selected>());
+        assert_eq!(1, tags!(Selected in world).count());
+        assert!(world[b].has::<Selected>());
+    }
+    #[test]
+    fn schedule() {
+        component! { Foo: u8 }
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("last")
+            .add_stage_before("last", "first")
+            .add_stage_after("first", "middle")
+            .add_system("last", |world: &mut World<Comp>| {
+                for foo in map_mut!(Foo in world) {
+                    *foo *= 2;
+                }
+            })
+            .add_system("first", |world: &mut World<Comp>| {
+                world.insert(entity! { Foo: 0 });
+            })
+            .add_system("middle", |world: &mut World<Comp>| {
+                for foo in map_mut!(Foo in world) {
+                    *foo += 1;
+                }
+            });
+        assert_eq!(
+            vec!["first", "middle", "last"],
+            schedule.stages().collect::<Vec<_>>()
+        );
+        let mut world = World::new();
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        let mut foos = map!(Foo in world).copied().collect::<Vec<_>>();
+        foos.sort();
+        assert_eq!(vec![2, 6], foos);
+    }
+    #[test]
+    fn par_schedule() {
+        component! { Foo: u8, Bar: u8, Baz: u8 }
+        let mut schedule = Schedule::new().with_stage("update");
+        schedule
+            .add_par_system(
+                "update",
+                (
+                    Access::new().write::<Foo>(),
+                    |mut world: WorldView<Comp>| {
+                        for foo in map_mut!(Foo in world) {
+                            *foo += 1;
+                        }
+                    },
+                ),
+            )
+            .add_par_system(
+                "update",
+                (
+                    Access::new().write::<Bar>(),
+                    |mut world: WorldView<Comp>| {
+                        for bar in map_mut!(Bar in world) {
+                            *bar += 1;
+                        }
+                    },
+                ),
+            )
+            .add_par_system(
+                "update",
+                (
+                    Access::new().read::<Foo>().write::<Baz>(),
+                    |mut world: WorldView<Comp>| {
+                        let sum: u8 = map!(Foo in world).sum();
+                        for baz in map_mut!(Baz in world) {
+                            *baz = sum;
+                        }
+                    },
+                ),
+            );
+        let conflicts = schedule.conflicts();
+        assert_eq!(1, conflicts.len());
+        assert_eq!(Some(vec!["Foo"]), conflicts[0].components);
+        schedule.add_system("update", |world: &mut World<Comp>| {
+            world.insert(entity! { Foo: 0, Bar: 0, Baz: 0 });
+        });
+        assert_eq!(4, schedule.conflicts().len());
+        let mut world = World::new();
+        world.insert(entity! { Foo: 0, Bar: 0, Baz: 0 });
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        let mut all = map!(Foo, Bar, Baz in world)
+            .map(|(foo, bar, baz)| (*foo, *bar, *baz))
+            .collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(vec![(0, 0, 0), (1, 1, 3), (2, 2, 3)], all);
+    }
+    #[test]
+    #[should_panic]
+    fn undeclared_access() {
+        component! { Foo: u8, Bar: u8 }
+        let mut world = World::new();
+        world.insert(entity! { Foo: 0, Bar: 0 });
+        Schedule::new()
+            .with_stage("update")
+            .with_par_system(
+                "update",
+                (Access::new().read::<Foo>(), |mut world: WorldView<Comp>| {
+                    for foo in map_mut!(Foo in world) {
+                        *foo += 1;
+                    }
+                }),
+            )
+            .run(&mut world);
+    }
+    #[test]
+    fn resources() {
+        component! { Foo: u8 }
+        struct Step(u8);
+        struct Total(u32);
+        let mut world = World::new();
+        assert_eq!(None, world.insert_resource(Step(2)).map(|step| step.0));
+        world.insert_resource(Total(0));
+        world.insert(entity! { Foo: 1 });
+        world.insert(entity! { Foo: 2 });
+        let mut schedule = Schedule::new().with_stage("update");
+        schedule
+            .add_par_system(
+                "update",
+                (
+                    Access::new().write::<Foo>().read_resource::<Step>(),
+                    |mut world: WorldView<Comp>| {
+                        let step = world.resource::<Step>().unwrap();
+                        for foo in map_mut!(Foo in world) {
+                            *foo += step.0;
+                        }
+                    },
+                ),
+            )
+            .add_par_system(
+                "update",
+                (
+                    Access::new().read::<Foo>().write_resource::<Total>(),
+                    |mut world: WorldView<Comp>| {
+                        let sum: u32 = map!(Foo in world).map(|&foo| foo as u32).sum();
+                        world.resource_mut::<Total>().unwrap().0 += sum;
+                    },
+                ),
+            );
+        assert_eq!(1, schedule.conflicts().len());
+        schedule.run(&mut world);
+        assert_eq!(7, world.resource::<Total>().unwrap().0);
+        world.resource_mut::<Step>().unwrap().0 = 0;
+        schedule.run(&mut world);
+        assert_eq!(14, world.remove_resource::<Total>().unwrap().0);
+        assert!(!world.contains_resource::<Total>());
+        assert!(world.contains_resource::<Step>());
+    }
+    #[test]
+    fn commands() {
+        component! { Foo: u8, Bar: u8 }
+        let mut world = World::new();
+        let a = world.insert(entity! { Foo: 1 });
+        let b = world.insert(entity! { Foo: 2, Bar: 2 });
+        let mut commands = Commands::new();
+        commands.despawn(a).remove::<Bar>(b).add::<Bar>(a, 1);
+        let c = commands.spawn(&world, entity! { Bar: 3 });
+        commands.add::<Foo>(c, 5);
+        let d = world.reserve_id();
+        world.insert(entity! { Foo: 4 });
+        commands.spawn_reserved(d, entity! { Bar: 4 });
+        assert_eq!(vec![c, d], commands.apply(&mut world));
+        assert!(!world.contains(a));
+        assert!(!world[b].has::<Bar>());
+        assert_eq!((5, 3), (world[c][Foo], world[c][Bar]));
+        assert_eq!(4, world[d][Bar]);
+        let mut schedule = Schedule::new().with_stage("update");
+        schedule.add_par_system(
+            "update",
+            (Access::new().read::<Bar>(), |mut world: WorldView<Comp>| {
+                let commands: Commands<_> = map!(Bar in world)
+                    .map(|&bar| {
+                        let mut commands = Commands::new();
+                        commands.spawn_reserved(world.reserve_id(), entity! { Foo: bar });
+                        commands
+                    })
+                    .collect();
+                world.commands().extend(Some(commands));
+            }),
+        );
+        schedule.run(&mut world);
+        assert_eq!(6, world.len());
+        assert_eq!(
+            vec![&3],
+            map!(Foo in world)
+                .filter(|&&foo| foo == 3)
+                .collect::<Vec<_>>()
+        );
+    }
+    #[test]
+    fn change_detection() {
+        component! { Foo: u8, Bar: u8, #storage(sparse) Baz: u8 }
+        let mut world = World::new();
+        let a = world.insert(entity! { Foo: 1, Bar: 1 });
+        let b = world.insert(entity! { Foo: 2, Baz: 2 });
+        assert_eq!(2, map!(Foo; Added<Foo> in world).count());
+        world.advance_tick();
+        assert_eq!(0, map!(Foo; Changed<Foo> in world).count());
+        world.get_mut(a).unwrap()[Foo] += 1;
+        *world.get_mut(b).unwrap().get_mut::<Baz>().unwrap() += 1;
+        assert_eq!(
+            vec![&2],
+            map!(Foo; Changed<Foo> in world).collect::<Vec<_>>()
+        );
+        assert_eq!(1, tags!(Foo; Changed<Baz> in world).count());
+        assert_eq!(0, tags!(Foo; Added<Foo> in world).count());
+        world.advance_tick();
+        world.get_mut(a).unwrap().add::<Baz>(0);
+        for bar in map_mut!(Bar in world) {
+            *bar += 1;
+        }
+        assert_eq!(1, tags!(Foo; Added<Baz>, Changed<Bar> in world).count());
+        assert_eq!(0, tags!(Foo; Changed<Foo> in world).count());
+        let mut schedule = Schedule::new().with_stage("update");
+        schedule
+            .add_par_system(
+                "update",
+                (
+                    Access::new().read::<Foo>().write::<Bar>(),
+                    |mut world: WorldView<Comp>| {
+                        for bar in map_mut!(Bar; Changed<Foo> in world) {
+                            *bar += 1;
+                        }
+                    },
+                ),
+            )
+            .add_system("update", |world: &mut World<Comp>| {
+                for (foo, _) in map_mut!(Foo, Baz; Added<Baz> in world) {
+                    *foo += 10;
+                }
+            });
+        schedule.run(&mut world);
+        assert_eq!((12, 3), (world[a][Foo], world[a][Bar]));
+        schedule.run(&mut world);
+        assert_eq!((12, 4), (world[a][Foo], world[a][Bar]));
+        schedule.run(&mut world);
+        assert_eq!((12, 4), (world[a][Foo], world[a][Bar]));
+        world.advance_tick();
+        world[b][Foo] += 1;
+        for mut entity in world.iter_mut() {
+            if entity.has::<Bar>() {
+                entity[Bar] += 1;
+            }
+        }
+        assert_eq!(
+            vec![b],
+            tags!(Foo; Changed<Foo> in world).ids().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![&5],
+            map!(Bar; Changed<Bar> in world).collect::<Vec<_>>()
+        );
+    }
+    #[test]
+    fn removal_detection() {
+        component! { Foo: u8, #storage(sparse) Bar: u8, Baz: () }
+        let mut world = World::new();
+        world.track_removed::<Foo>();
+        world.track_removed::<Bar>();
+        let a = world.insert(entity! { Foo: 1, Bar: 1, Baz: () });
+        let b = world.insert(entity! { Foo: 2, Bar: 2 });
+        world.get_mut(a).unwrap().remove::<Baz>();
+        world.get_mut(a).unwrap().remove::<Bar>();
+        world.get_mut(a).unwrap().remove::<Bar>();
+        world.remove(b);
+        assert_eq!(vec![a, b], world.removed::<Bar>().collect::<Vec<_>>());
+        assert_eq!(vec![b], world.removed::<Foo>().collect::<Vec<_>>());
+        world.advance_tick();
+        assert_eq!(0, world.removed::<Bar>().count());
+        let mut schedule = Schedule::new().with_stage("update");
+        schedule.add_par_system(
+            "update",
+            (Access::new(), |mut world: WorldView<Comp>| {
+                let removed: Vec<_> = world.removed::<Foo>().collect();
+                for id in removed {
+                    world.spawn(entity! { Bar: id.index() as u8 });
+                }
+            }),
+        );
+        world.remove(a);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(vec![&0], map!(Bar in world).collect::<Vec<_>>());
+    }
+    #[test]
+    fn untracked_removals() {
+        component! { Foo: () }
+        let mut world = World::new();
+        let a = world.insert(entity! { Foo: () });
+        let b = world.insert(entity! { Foo: () });
+        world.remove(a);
+        assert_eq!(0, world.removed::<Foo>().count());
+        world.track_removed::<Foo>();
+        world.remove(b);
+        assert_eq!(vec![b], world.removed::<Foo>().collect::<Vec<_>>());
+    }
+    #[test]
+    fn exclusion() {
+        component! { Foo: u8, Bar: u8, Frozen: (), #storage(sparse) Hidden: () }
+        let mut world = World::new();
+        world.insert(entity! { Foo: 1, Bar: 1 });
+        world.insert(entity! { Foo: 2, Bar: 2, Frozen: () });
+        world.insert(entity! { Foo: 3, Bar: 3, Hidden: () });
+        world.insert(entity! { Foo: 4, Frozen: (), Hidden: () });
+        for (foo, bar) in map_mut!(Foo, Bar; !Frozen in world) {
+            *foo += *bar;
+        }
+        let mut foos = map!(Foo in world).copied().collect::<Vec<_>>();
+        foos.sort();
+        assert_eq!(vec![2, 2, 4, 6], foos);
+        assert_eq!(
+            vec![&2],
+            map!(Foo; !Frozen, !Hidden in world).collect::<Vec<_>>()
+        );
+        assert_eq!(2, tags!(Foo; !Hidden in world).count());
+        assert_eq!(1, tags!(Foo; Without<Bar> in world).count());
+        let mut schedule = Schedule::new().with_stage("update");
+        schedule.add_par_system(
+            "update",
+            (
+                Access::new().read::<Foo>().read::<Bar>().read::<Hidden>(),
+                |mut world: WorldView<Comp>| {
+                    let visible: Vec<Id> = tags!(Foo, Bar; !Hidden in world).collect();
+                    assert_eq!(2, visible.len());
+                    assert_eq!(3, tags!(Bar in world).count());
+                    for id in visible {
+                        world.commands().add::<Frozen>(id, ());
+                    }
+                },
+            ),
+        );
+        schedule.run(&mut world);
+        assert_eq!(3, tags!(Foo, Frozen in world).count());
+    }
+    #[test]
+    fn optional() {
+        component! { Position: u8, Tint: u8, Frozen: (), #storage(sparse) Glow: u8 }
+        let mut world = World::new();
+        world.insert(entity! { Position: 1 });
+        world.insert(entity! { Position: 2, Tint: 20 });
+        world.insert(entity! { Position: 3, Glow: 30 });
+        world.insert(entity! { Tint: 40 });
+        for (tint, glow) in map_mut!(Option<Tint>, Option<Glow>; !Frozen in world) {
+            if let Some(tint) = tint {
+                *tint += 1;
+            }
+            if let Some(glow) = glow {
+                *glow += 1;
+            }
+        }
+        let mut rendered = map!(Position, Option<Tint>, Option<Glow> in world)
+            .map(|(position, tint, glow)| (*position, tint.copied(), glow.copied()))
+            .collect::<Vec<_>>();
+        rendered.sort();
+        assert_eq!(
+            vec![(1, None, None), (2, Some(21), None), (3, None, Some(31))],
+            rendered
+        );
+        let entity = entity! { Position: 4 };
+        assert_eq!(Some((&4, None)), map!(Position, Option<Tint>)(&entity));
+        assert_eq!(None, map!(Tint, Option<Position>)(&entity));
+    }
+    #[test]
+    fn ids() {
+        component! { Position: u8, Speed: u8 }
+        let mut world = World::new();
+        let a = world.insert(entity! { Position: 1, Speed: 1 });
+        let b = world.insert(entity! { Position: 2 });
+        for (id, position, speed) in map_mut!(id, Position, Speed in world) {
+            assert_eq!(a, id);
+            *position += *speed;
+        }
+        let mut positions = map!(id, Position in world).collect::<Vec<_>>();
+        positions.sort();
+        assert_eq!(vec![(a, &2), (b, &2)], positions);
+        assert_eq!(vec![b], map!(id; !Speed in world).collect::<Vec<_>>());
+        assert_eq!(Some((b, &2)), map!(id, Position)(&world[b]));
+        assert_eq!(None, map!(id, Position)(&entity! { Position: 3 }));
+    }
+    #[test]
+    fn cached_query() {
+        component! { Foo: u8, Bar: u8, Baz: (), #storage(sparse) Qux: () }
+        let mut world = World::new();
+        world.insert(entity! { Foo: 1, Bar: 1 });
+        world.insert(entity! { Foo: 1 });
+        let mut query = Query::<(Write<Foo>, Read<Bar>), Without<Baz>>::new();
+        fn step(query: &mut Query<(Write<Foo>, Read<Bar>), Without<Baz>>, world: &mut World<Comp>) {
+            for (foo, bar) in query.iter_mut(world) {
+                *foo += *bar;
+            }
+        }
+        step(&mut query, &mut world);
+        world.insert(entity! { Foo: 1, Bar: 2, Baz: () });
+        world.insert(entity! { Foo: 1, Bar: 3, Qux: () });
+        step(&mut query, &mut world);
+        let mut foos = map!(Foo in world).copied().collect::<Vec<_>>();
+        foos.sort();
+        assert_eq!(vec![1, 1, 3, 4], foos);
+        let mut other = World::new();
+        other.insert(entity! { Foo: 1, Bar: 1, Qux: () });
+        step(&mut query, &mut other);
+        assert_eq!(
+            vec![&2],
+            query.iter(&other).map(|(foo, _)| foo).collect::<Vec<_>>()
+        );
+        let mut sparse = Query::<(Id, Read<Qux>)>::new();
+        assert_eq!(1, sparse.iter(&world).count());
+        assert_eq!(1, sparse.entities(&other).count());
+
+        let mut schedule = Schedule::new().with_stage("update");
+        let mut query = Query::<(Write<Foo>, Read<Bar>)>::new();
+        let access = query.access();
+        schedule.add_par_system(
+            "update",
+            (access, move |mut view: WorldView<Comp>| {
+                for (foo, bar) in query.iter_view_mut(&mut view) {
+                    *foo *= *bar;
+                }
+            }),
+        );
+        schedule.run(&mut other);
+        assert_eq!(vec![&2], map!(Foo in other).collect::<Vec<_>>());
+    }
+    #[test]
+    fn hierarchy() {
+        component! { Name: String }
+        let mut world = World::new();
+        let tank = world.insert(entity! { Name: "tank".to_string() });
+        let turret = world.insert(entity! { Name: "turret".to_string() });
+        let barrel = world.insert(entity! { Name: "barrel".to_string() });
+        let hatch = world.insert(entity! { Name: "hatch".to_string() });
+        let other = world.insert(entity! { Name: "other".to_string() });
+        assert!(world.set_parent(turret, tank));
+        assert!(world.set_parent(barrel, turret));
+        assert!(world.set_parent(hatch, other));
+        assert!(world.set_parent(hatch, turret));
+        assert!(!world.set_parent(tank, barrel));
+        assert!(!world.set_parent(tank, tank));
+        assert_eq!(Some(turret), world.parent(barrel));
+        assert_eq!(&[barrel, hatch], world.children(turret));
+        assert!(world.children(other).is_empty());
+        assert_eq!(
+            vec![turret, tank],
+            world.ancestors(barrel).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![turret, barrel, hatch],
+            world.descendants(tank).collect::<Vec<_>>()
+        );
+        world.remove(turret);
+        assert_eq!(None, world.parent(barrel));
+        assert!(world.children(tank).is_empty());
+        world.set_parent(barrel, tank);
+        world.set_parent(hatch, barrel);
+        let removed = world.remove_recursive(tank);
+        assert_eq!(
+            vec!["tank", "barrel", "hatch"],
+            removed
+                .iter()
+                .map(|entity| entity[Name].as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, world.len());
+        let mut commands = Commands::new();
+        let child = world.insert(entity! { Name: "child".to_string() });
+        commands.set_parent(child, other).despawn_recursive(other);
+        commands.apply(&mut world);
+        assert!(world.is_empty());
+    }
+    #[test]
+    fn relations() {
+        component! { Foo: () }
+        struct Likes;
+        impl Relation for Likes {}
+        struct DockedAt;
+        impl Relation for DockedAt {
+            const ON_TARGET_REMOVED: OnTargetRemoved = OnTargetRemoved::Remove;
+        }
+        struct Remembers;
+        impl Relation for Remembers {
+            const ON_TARGET_REMOVED: OnTargetRemoved = OnTargetRemoved::Keep;
+        }
+        let mut world = World::new();
+        let a = world.insert(entity! { Foo: () });
+        let b = world.insert(entity! { Foo: () });
+        let c = world.insert(entity! { Foo: () });
+        let d = world.insert(entity! { Foo: () });
+        assert!(world.relate::<Likes>(a, b));
+        assert!(world.relate::<Likes>(a, c));
+        assert!(world.relate::<Likes>(c, b));
+        assert!(!world.relate::<Likes>(a, b));
+        assert!(world.relate::<DockedAt>(d, c));
+        assert!(world.relate::<Remembers>(a, c));
+        assert_eq!(&[b, c], world.targets::<Likes>(a));
+        assert_eq!(&[a, c], world.sources::<Likes>(b));
+        assert!(world.sources::<DockedAt>(b).is_empty());
+        assert!(world.unrelate::<Likes>(c, b));
+        assert!(!world.unrelate::<Likes>(c, b));
+        assert_eq!(&[a], world.sources::<Likes>(b));
+        let mut liking = Query::<Id, Related<Likes>>::new();
+        assert_eq!(vec![a], liking.iter(&world).collect::<Vec<_>>());
+        assert_eq!(1, tags!(Foo; Related<DockedAt> in world).count());
+
+        world.remove(c);
+        assert!(!world.contains(d));
+        assert_eq!(&[b], world.targets::<Likes>(a));
+        assert_eq!(&[c], world.targets::<Remembers>(a));
+        assert_eq!(
+            vec![(a, c)],
+            world.dangling::<Remembers>().collect::<Vec<_>>()
+        );
+        assert_eq!(0, world.dangling::<Likes>().count());
+
+        let mut commands = Commands::new();
+        commands.unrelate::<Remembers>(a, c).relate::<Likes>(b, a);
+        commands.apply(&mut world);
+        assert_eq!(0, world.dangling::<Remembers>().count());
+        assert_eq!(&[b], world.sources::<Likes>(a));
+        world.remove(b);
+        assert!(world.targets::<Likes>(a).is_empty());
+        assert!(world.sources::<Likes>(a).is_empty());
+
+        // A long chain is removed without overflowing the stack
+        let root = world.insert(entity! { Foo: () });
+        let mut last = root;
+        for _ in 0..100_000 {
+            let next = world.insert(entity! { Foo: () });
+            world.relate::<DockedAt>(next, last);
+            last = next;
+        }
+        world.remove(root);
+        assert!(!world.contains(last));
+        assert_eq!(1, world.len());
+    }
+    #[test]
+    fn dangling_references() {
+        component! { Foo: (), Target: Id, #storage(sparse) Followers: Vec<Id> }
+        let mut other = World::new();
+        other.insert(entity! { Foo: () });
+        other.insert(entity! { Foo: () });
+        let stranger = other.insert(entity! { Foo: () });
+        let mut world = World::new();
+        let a = world.insert(entity! { Foo: () });
+        let b = world.insert(entity! { Target: a, Followers: vec![a, stranger] });
+        assert_eq!(IdStatus::Alive, world.status(a));
+        assert_eq!(IdStatus::NeverExisted, world.status(stranger));
+        assert_eq!(
+            vec![DanglingReference {
+                source: b,
+                component: "Followers",
+                target: stranger,
+                status: IdStatus::NeverExisted,
+            }],
+            world.dangling_references()
+        );
+        world.remove(a);
+        let c = world.insert(entity! { Foo: () });
+        assert_eq!(a.index(), c.index());
+        assert_eq!(IdStatus::Removed, world.status(a));
+        let mut dangling = world.dangling_references();
+        dangling.sort_by_key(|reference| (reference.component, reference.target));
+        assert_eq!(
+            vec![("Followers", a), ("Followers", stranger), ("Target", a)],
+            dangling
+                .iter()
+                .map(|reference| (reference.component, reference.target))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "Target of entity 1v0 refers to entity 0v0, which is stale: its index has a newer generation",
+            dangling[2].to_string()
+        );
+    }
+    #[test]
+    #[should_panic(expected = "is not reserved")]
+    fn spawn_unreserved() {
+        component! { Foo: () }
+        let mut world = World::new();
+        let mut other = World::new();
+        other.insert(entity! { Foo: () });
+        let id = other.reserve_id();
+        let mut commands = Commands::new();
+        commands.spawn_reserved(id, entity! { Foo: () });
+        commands.apply(&mut world);
+    }
+    #[test]
+    #[should_panic(expected = "is stale")]
+    fn removed_index() {
+        component! { Foo: () }
+        let mut world = World::new();
+        let id = world.insert(entity! { Foo: () });
+        world.remove(id);
+        let _ = &world[id];
+    }
+    #[test]
+    fn events() {
+        component! { Foo: () }
+        #[derive(Debug, PartialEq)]
+        struct Hit(u32);
+        let mut world = World::<Comp>::new();
+        let mut a = EventReader::<Hit>::new();
+        let mut b = EventReader::<Hit>::new();
+        assert_eq!(0, world.read_events(&mut a).count());
+        world.send_event(Hit(1));
+        world.send_event(Hit(2));
+        assert_eq!(
+            vec![&Hit(1), &Hit(2)],
+            world.read_events(&mut a).collect::<Vec<_>>()
+        );
+        assert_eq!(0, world.read_events(&mut a).count());
+        world.update_events();
+        world.send_event(Hit(3));
+        assert_eq!(vec![&Hit(3)], world.read_events(&mut a).collect::<Vec<_>>());
+        assert_eq!(3, world.read_events(&mut b).count());
+        world.update_events();
+        world.update_events();
+        world.send_event(Hit(4));
+        // A reader that was not used for two updates misses the dropped events
+        let mut late = EventReader::<Hit>::new();
+        assert_eq!(
+            vec![&Hit(4)],
+            world.read_events(&mut late).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![&Hit(4)], world.read_events(&mut a).collect::<Vec<_>>());
+
+        let mut commands = Commands::new();
+        commands.send_event(Hit(5));
+        commands.apply(&mut world);
+        assert_eq!(vec![&Hit(5)], world.read_events(&mut a).collect::<Vec<_>>());
+
+        // Every system sees every event once, even if it runs before the sender
+        let mut world = World::<Comp>::new();
+        world.insert_resource(Vec::<(&str, u32)>::new());
+        let mut before = EventReader::<Hit>::new();
+        let mut after = EventReader::<Hit>::new();
+        let mut sent = 0;
+        let mut schedule = Schedule::new()
+            .with_stage("first")
+            .with_stage("second")
+            .with_system("first", move |world: &mut World<Comp>| {
+                let hits: Vec<u32> = world.read_events(&mut before).map(|hit| hit.0).collect();
+                let seen = world.resource_mut::<Vec<(&str, u32)>>().unwrap();
+                seen.extend(hits.into_iter().map(|hit| ("before", hit)));
+            })
+            .with_system("second", move |world: &mut World<Comp>| {
+                sent += 1;
+                world.send_event(Hit(sent));
+            })
+            .with_par_system(
+                "second",
+                (
+                    Access::new()
+                        .read_resource::<Events<Hit>>()
+                        .write_resource::<Vec<(&'static str, u32)>>(),
+                    move |mut view: WorldView<Comp>| {
+                        let hits: Vec<u32> =
+                            view.read_events(&mut after).map(|hit| hit.0).collect();
+                        let seen = view.resource_mut::<Vec<(&str, u32)>>().unwrap();
+                        seen.extend(hits.into_iter().map(|hit| ("after", hit)));
+                    },
+                ),
+            );
+        for _ in 0..3 {
+            schedule.run(&mut world);
+        }
+        assert_eq!(
+            vec![
+                ("after", 1),
+                ("before", 1),
+                ("after", 2),
+                ("before", 2),
+                ("after", 3)
+            ],
+            world.remove_resource::<Vec<(&str, u32)>>().unwrap()
+        );
+    }
+    #[test]
+    fn scene() {
+        component! { #[derive(Clone)] Name: String, Target: Id, #storage(sparse) Selected: () }
+        let mut editor = World::new();
+        let outside = editor.insert(entity! { Name: "outside".into() });
+        let root = editor.insert(entity! { Name: "root".into(), Target: outside, Selected: () });
+        let child = editor.insert(entity! { Name: "child".into(), Target: root });
+        editor.set_parent(child, root);
+        editor.set_parent(root, outside);
+        let removed = editor.insert(entity! { Name: "removed".into() });
+        editor.remove(removed);
+        let scene = Scene::extract(&editor, vec![root, child, child, removed]);
+        assert_eq!(2, scene.len());
+        assert_eq!(
+            vec![root, child],
+            scene.iter().map(|(id, _)| id).collect::<Vec<_>>()
+        );
+
+        let mut world = World::new();
+        let player = world.insert(entity! { Name: "player".into() });
+        assert_eq!(vec![outside], scene.external_ids());
+        let error = scene.instantiate(&mut world).unwrap_err();
+        assert_eq!(ExternalIds(vec![outside]), error);
+        assert_eq!(
+            "The scene refers to entities outside of it: 0v0",
+            error.to_string()
+        );
+        assert_eq!(1, world.len());
+        let first = scene.instantiate_with(&mut world, |_| player);
+        let second = scene.instantiate_with(&mut world, |_| player);
+        assert_eq!(5, world.len());
+        for ids in &[first, second] {
+            assert_eq!(ids[&root], world[ids[&child]][Target]);
+            assert_eq!(player, world[ids[&root]][Target]);
+            assert!(world[ids[&root]].has::<Selected>());
+            assert_eq!(&[ids[&child]], world.children(ids[&root]));
+            assert_eq!(None, world.parent(ids[&root]));
+        }
+    }
+    #[test]
+    fn hooks() {
+        use std::sync::{Arc, Mutex};
+        component! { Foo: u8, #storage(sparse) Bar: u8, Baz: () }
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::new();
+        for &kind in &["add", "insert", "remove"] {
+            let log_foo = log.clone();
+            let log_bar = log.clone();
+            let hook_foo = move |id: Id, value: &u8, _: &mut Commands<Comp>| {
+                log_foo.lock().unwrap().push((kind, "foo", id, *value))
+            };
+            let hook_bar = move |id: Id, value: &u8, _: &mut Commands<Comp>| {
+                log_bar.lock().unwrap().push((kind, "bar", id, *value))
+            };
+            match kind {
+                "add" => {
+                    world.on_add::<Foo, _>(hook_foo);
+                    world.on_add::<Bar, _>(hook_bar);
+                }
+                "insert" => {
+                    world.on_insert::<Foo, _>(hook_foo);
+                    world.on_insert::<Bar, _>(hook_bar);
+                }
+                _ => {
+                    world.on_remove::<Foo, _>(hook_foo);
+                    world.on_remove::<Bar, _>(hook_bar);
+                }
+            }
+        }
+        // Hooks can change the world once the change that ran them is done
+        world.on_add::<Bar, _>(|id, _, commands| {
+            commands.add::<Baz>(id, ());
+        });
+        world.on_remove::<Bar, _>(|id, _, commands| {
+            commands.remove::<Baz>(id);
+        });
+        let take = || std::mem::take(&mut *log.lock().unwrap());
+
+        let a = world.insert(entity! { Foo: 1 });
+        assert_eq!(vec![("add", "foo", a, 1), ("insert", "foo", a, 1)], take());
+        let b = world.insert(entity! { Foo: 2, Bar: 3 });
+        assert_eq!(
+            vec![
+                ("add", "foo", b, 2),
+                ("add", "bar", b, 3),
+                ("insert", "foo", b, 2),
+                ("insert", "bar", b, 3),
+            ],
+            take()
+        );
+        assert!(world[b].has::<Baz>());
+
+        let mut entity = world.get_mut(a).unwrap();
+        assert_eq!(Some(1), entity.add::<Foo>(4));
+        assert_eq!(vec![("insert", "foo", a, 4)], take());
+        assert_eq!(None, entity.add::<Bar>(5));
+        assert_eq!(vec![("add", "bar", a, 5), ("insert", "bar", a, 5)], take());
+        assert!(!entity.has::<Baz>());
+        drop(entity);
+        assert!(world[a].has::<Baz>());
+
+        assert_eq!(Some(5), world.get_mut(a).unwrap().remove::<Bar>());
+        assert_eq!(vec![("remove", "bar", a, 5)], take());
+        assert!(!world[a].has::<Baz>());
+        assert_eq!(None, world.get_mut(a).unwrap().remove::<Bar>());
+        assert!(take().is_empty());
+
+        world.remove(b);
+        assert_eq!(
+            vec![("remove", "foo", b, 2), ("remove", "bar", b, 3)],
+            take()
+        );
+        // Detached `Entity`s don't run hooks
+        let mut entity = entity! { Foo: 6 };
+        entity.add::<Bar>(7);
+        assert!(take().is_empty());
+    }
+    #[test]
+    fn observers() {
+        component! { Health: i32 }
+        struct Damage(i32);
+        struct Heal(i32);
+        let mut world = World::new();
+        world.insert_resource(Vec::<(&str, Id)>::new());
+        world.observe(
+            |target, damage: &Damage, world: &mut World<Comp>, commands| {
+                world
+                    .resource_mut::<Vec<(&str, Id)>>()
+                    .unwrap()
+                    .push(("global", target));
+                *world.get_mut(target).unwrap().get_mut::<Health>().unwrap() -= damage.0;
+                if world[target][Health] <= 0 {
+                    commands.despawn(target);
+                }
+            },
+        );
+        let a = world.insert(entity! { Health: 10 });
+        let b = world.insert(entity! { Health: 10 });
+        assert!(
+            world.observe_entity(a, |target, _: &Damage, world, commands| {
+                world
+                    .resource_mut::<Vec<(&str, Id)>>()
+                    .unwrap()
+                    .push(("a", target));
+                // Commands are applied after every observer has run
+                commands.push(move |world| {
+                    let log = world.resource_mut::<Vec<(&str, Id)>>().unwrap();
+                    log.push(("commands", target));
+                });
+                // Observers can trigger more events right away
+                world.trigger(target, Heal(1));
+            })
+        );
+        world.observe(|target, heal: &Heal, world: &mut World<Comp>, _| {
+            *world.get_mut(target).unwrap().get_mut::<Health>().unwrap() += heal.0;
+        });
+
+        world.trigger(a, Damage(5));
+        world.trigger(b, Damage(3));
+        assert_eq!(
+            vec![("a", a), ("global", a), ("commands", a), ("global", b)],
+            std::mem::take(world.resource_mut::<Vec<(&str, Id)>>().unwrap())
+        );
+        assert_eq!(6, world[a][Health]);
+        assert_eq!(7, world[b][Health]);
+
+        // Triggered events can be recorded in `Commands`
+        let mut commands = Commands::new();
+        commands.trigger(b, Damage(7));
+        commands.apply(&mut world);
+        assert!(!world.contains(b));
+        world.trigger(b, Damage(1));
+        assert!(!world.observe_entity(b, |_, _: &Damage, _, _| {}));
+
+        // Observers of an `Entity` are removed with it
+        world.remove(a);
+        world.resource_mut::<Vec<(&str, Id)>>().unwrap().clear();
+        let c = world.insert(entity! { Health: 10 });
+        assert_eq!(a.index, c.index);
+        world.trigger(c, Damage(1));
+        assert_eq!(
+            &vec![("global", c)],
+            world.resource::<Vec<(&str, Id)>>().unwrap()
+        );
+
+        // Observers stop running once the target is removed
+        world.resource_mut::<Vec<(&str, Id)>>().unwrap().clear();
+        let d = world.insert(entity! { Health: 10 });
+        world.observe_entity(d, |target, _: &Damage, world, _| {
+            world.remove(target);
+        });
+        world.trigger(d, Damage(1));
+        assert!(!world.contains(d));
+        assert!(world.resource::<Vec<(&str, Id)>>().unwrap().is_empty());
+    }
+    #[test]
+    #[cfg(feature = "f_uuid")]
+    fn uuid() {
+        component! { Foo: u8 }
+        let entity = entity! { Foo: 1 };
+        let uuid = entity.uuid();
+        let mut world = World::new();
+        let id = world.insert(entity);
+        assert_eq!(Some(id), world.find_uuid(uuid));
+        let entity = world.remove(id).unwrap();
+        assert_eq!(None, world.find_uuid(uuid));
+        let mut other = World::new();
+        let id = other.insert(entity);
+        assert_eq!(uuid, other[id].uuid());
+    }
+    #[test]
+    #[cfg(feature = "f_serde")]
+    fn serde() {
+        component! { #[derive(Debug, Clone, PartialEq)] #serde Position: i32, Target: Id, #storage(sparse) Frozen: () }
+        // Components without `#serde` don't need to be serializable
+        mod plain {
+            use super::*;
+            component! { Started: std::time::Instant }
+        }
+        let entity = entity! { Position: 1, Frozen: () };
+        let json = serde_json::to_value(&entity).unwrap();
+        assert_eq!(Some(&serde_json::json!(1)), json.get("Position"));
+        assert_eq!(Some(&serde_json::json!(null)), json.get("Frozen"));
+        let back: Entity<Comp> = serde_json::from_value(json).unwrap();
+        assert_eq!(Some(&1), back.get::<Position>());
+        assert!(back.has::<Frozen>());
+        let error = serde_json::from_str::<Entity<Comp>>(r#"{"Speed": 1}"#)
+            .map(drop)
+            .unwrap_err();
+        assert!(error.to_string().contains("unknown field `Speed`"));
+
+        let mut world = World::new();
+        let a = world.insert(entity! { Position: 1 });
+        let removed = world.insert(entity! { Position: 2 });
+        let b = world.insert(entity! { Position: 3, Target: a, Frozen: () });
+        world.set_parent(b, a);
+        // Never applied, so its reserved `Id` is saved as free
+        Commands::new().spawn(&world, entity! { Position: 5 });
+        world.remove(removed);
+        assert_eq!(serde_json::json!("2v0"), serde_json::to_value(b).unwrap());
+        let json = serde_json::to_string(&world).unwrap();
+        let mut loaded: World<Comp> = serde_json::from_str(&json).unwrap();
+        assert_eq!(2, loaded.len());
+        assert_eq!(IdStatus::Removed, loaded.status(removed));
+        assert_eq!(Some(a), map!(Target in loaded).next().copied());
+        assert_eq!(Some(&3), loaded[b].get::<Position>());
+        assert_eq!(1, tags!(Frozen in loaded).count());
+        assert_eq!(&[b], loaded.children(a));
+        let c = loaded.insert(entity! { Position: 4 });
+        assert_eq!(removed.index(), c.index());
+        assert_ne!(removed, c);
+        assert!(serde_json::from_str::<World<Comp>>(
+            r#"{"generations": [0], "free": [0], "entities": {"0v0": {}}, "children": {}}"#
+        )
+        .is_err());
+
+        let scene = Scene::extract(&loaded, vec![a, b]);
+        let json = serde_json::to_string(&scene).unwrap();
+        assert_eq!(scene, serde_json::from_str(&json).unwrap());
+        assert!(serde_json::from_str::<Scene<Comp>>(
+            r#"{"entities": {"0v0": {}}, "children": {"0v0": ["0v0"]}}"#
+        )
+        .is_err());
+    }
+    #[test]
+    #[cfg(all(feature = "f_serde", feature = "f_uuid"))]
+    fn serde_uuid_key() {
+        component! {
+            #serde
+            #unit #[allow(non_camel_case_types)]
+            #variant #[allow(non_camel_case_types)]
+            uuid: u8,
+            Foo: u8,
+        }
+        let entity = entity! { uuid: 1, Foo: 2 };
+        let error = serde_json::to_string(&entity).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("same name as the key of the uuid"));
+        let mut world = World::new();
+        let id = world.insert(entity);
+        assert!(serde_json::to_string(&world.entity(id)).is_err());
+        let json = r#"{"uuid": 1, "Foo": 2}"#;
+        assert!(serde_json::from_str::<Entity<Comp>>(json).is_err());
+    }
+    #[test]
+    #[cfg(feature = "f_snapshot")]
+    fn snapshot() {
+        component! { #serde Position: i32, Target: Id, #storage(sparse) Frozen: () }
+        let mut world = World::new();
+        let a = world.insert(entity! { Position: 1 });
+        let removed = world.insert(entity! { Position: 2 });
+        let b = world.insert(entity! { Position: 3, Target: a, Frozen: () });
+        world.set_parent(b, a);
+        world.remove(removed);
+        let snapshot = world.to_snapshot().unwrap();
+        assert!(snapshot.len() < serde_json::to_vec(&world).unwrap().len());
+        let loaded = World::<Comp>::from_snapshot(&snapshot).unwrap();
+        assert_eq!(2, loaded.len());
+        assert_eq!(IdStatus::Removed, loaded.status(removed));
+        assert_eq!(a, loaded[b][Target]);
+        assert!(loaded[b].has::<Frozen>());
+        assert_eq!(&[b], loaded.children(a));
+        #[cfg(feature = "f_uuid")]
+        assert_eq!(Some(b), loaded.find_uuid(world[b].uuid()));
+
+        assert_eq!(
+            Err(SnapshotError::NotASnapshot),
+            World::<Comp>::from_snapshot(b"{}").map(drop)
+        );
+        assert_eq!(
+            Err(SnapshotError::UnsupportedVersion(99)),
+            World::<Comp>::from_snapshot(b"EKSS\x63\x00").map(drop)
+        );
+        assert!(matches!(
+            World::<Comp>::from_snapshot(&snapshot[..snapshot.len() - 1]),
+            Err(SnapshotError::Invalid(_))
+        ));
+        mod current {
+            use super::*;
+            component! { #serde Position: i32, Speed: f32 }
+        }
+        let error = World::<current::Comp>::from_snapshot(&snapshot)
+            .map(drop)
+            .unwrap_err();
+        assert_eq!(
+            "The snapshot has components that are not in the component enum: Target, Frozen",
+            error.to_string()
+        );
+    }
+    #[test]
+    #[cfg(feature = "f_snapshot")]
+    fn migration() {
+        mod v0 {
+            use super::*;
+            component! { #serde Position: i32, Speed: i32, Trail: u8 }
+            pub fn save() -> Vec<u8> {
+                let mut world = World::new();
+                world.insert(entity! { Position: 1, Speed: 2, Trail: 7 });
+                world.insert(entity! { Position: 300 });
+                world.to_snapshot().unwrap()
+            }
+        }
+        // `Trail` was removed from the enum and `Name` was added
+        component! { #serde #version(1) X: i64, #version(1) Speed: f32, Name: String }
+        let snapshot = v0::save();
+        let rename = Schema::new(1).with_migration(0, |snapshot| {
+            snapshot.rename("Position", "X");
+            Ok(())
+        });
+        assert_eq!(
+            Err(SnapshotError::UnknownComponents(vec!["Trail".into()])),
+            World::<Comp>::from_snapshot_with(&snapshot, &rename).map(drop)
+        );
+        let rename = Schema::new(1).with_migration(0, |snapshot| {
+            snapshot.rename("Position", "X");
+            snapshot.remove("Trail");
+            Ok(())
+        });
+        assert_eq!(
+            Err(SnapshotError::VersionMismatch {
+                component: "X".into(),
+                expected: 1,
+                found: 0,
+            }),
+            World::<Comp>::from_snapshot_with(&snapshot, &rename).map(drop)
+        );
+        let mut schema = Schema::new(2);
+        schema.add_migration(0, |snapshot| {
+            assert_eq!(0, snapshot.schema());
+            assert_eq!(Some(0), snapshot.version("Speed"));
+            snapshot.transform("Speed", |speed: i32| speed as f32 * 0.5)?;
+            snapshot.set_version("Speed", 1);
+            Ok(())
+        });
+        assert_eq!(
+            Err(SnapshotError::MissingMigration(1)),
+            World::<Comp>::from_snapshot_with(&snapshot, &schema).map(drop)
+        );
+        schema.add_migration(1, |snapshot| {
+            snapshot.remove("Trail");
+            snapshot.rename("Position", "X");
+            snapshot.transform("X", |x: i32| i64::from(x))?;
+            snapshot.set_version("X", 1);
+            Ok(())
+        });
+        let world = World::<Comp>::from_snapshot_with(&snapshot, &schema).unwrap();
+        let mut values: Vec<_> = world
+            .iter()
+            .map(|entity| (entity[X], entity.get::<Speed>().copied()))
+            .collect();
+        values.sort_by_key(|&(x, _)| x);
+        assert_eq!(vec![(1, Some(1.0)), (300, None)], values);
+        assert_eq!(0, tags!(Name in world).count());
+        let newer = world.to_snapshot_with(&schema).unwrap();
+        assert_eq!(
+            Err(SnapshotError::UnsupportedSchema(2)),
+            World::<Comp>::from_snapshot(&newer).map(drop)
+        );
+        let failing = Schema::new(1).with_migration(0, |snapshot| {
+            snapshot.transform("Speed", |speed: String| speed)
+        });
+        assert!(matches!(
+            World::<Comp>::from_snapshot_with(&snapshot, &failing),
+            Err(SnapshotError::Invalid(_))
+        ));
+        // 300 takes several bytes, and decoding it as a `u8` only reads the first one
+        let truncating =
+            Schema::new(1).with_migration(0, |snapshot| snapshot.transform("Position", |x: u8| x));
+        assert!(matches!(
+            World::<Comp>::from_snapshot_with(&snapshot, &truncating),
+            Err(SnapshotError::Invalid(_))
+        ));
+    }
+    #[test]
     #[cfg(feature = "f_rayon")]
     fn rayon() {
-        component! { Foo: (), Bar: () }
+        use rayon::prelude::*;
+        component! { Foo: (), #storage(sparse) Bar: () }
         let mut world = World::new();
-        for _ in 0..100 {
-            world.insert(entity!(Foo: ()));
+        for i in 0..100 {
+            let id = world.insert(entity!(Foo: ()));
+            if i % 2 == 0 {
+                world.get_mut(id).unwrap().add::<Bar>(());
+            }
         }
         assert_eq!(100, tags!(Foo in par world).count());
+        assert_eq!(50, tags!(Foo, Bar in par world).count());
+        assert_eq!(50, tags!(Foo; !Bar in par world).count());
+        let commands: Commands<_> = map!(id, Bar in par world)
+            .map(|(id, _)| {
+                let mut commands = Commands::new();
+                commands.despawn(id);
+                commands
+            })
+            .collect();
+        assert_eq!(50, commands.len());
+        commands.apply(&mut world);
+        assert_eq!(50, world.len());
+        assert_eq!(0, tags!(Bar in par world).count());
+        let ids: Vec<_> = map_mut!(id, Foo in par world).map(|(id, _)| id).collect();
+        assert!(ids.into_iter().all(|id| world.contains(id)));
+        let mut query = Query::<(Id, Write<Foo>), Without<Bar>>::new();
+        assert_eq!(50, query.par_iter_mut(&mut world).count());
+        assert_eq!(50, query.par_entities(&world).count());
+        let changed = (&mut world)
+            .into_par_iter()
+            .filter_map(|mut entity| entity.get_mut::<Foo>().map(|_| ()))
+            .count();
+        assert_eq!(50, changed);
+        assert_eq!(50, tags!(Foo; Changed<Foo> in par world).count());
     }
 }
@@ -9,6 +9,52 @@ macro_rules! require_rayon {
     }};
 }
 
+#[macro_export]
+#[doc(hidden)]
+macro_rules! component_set {
+    (@ [$($t:ty,)*] id $(, $($rest:tt)*)?) => {
+        eks::component_set!(@ [$($t,)* eks::Id,] $($($rest)*)?)
+    };
+    (@ [$($t:ty,)*] $id:ident $(<$opt:ident>)? $(, $($rest:tt)*)?) => {
+        eks::component_set!(@ [$($t,)* $id $(<$opt>)?,] $($($rest)*)?)
+    };
+    (@ [$($t:ty,)*]) => {
+        ($($t),*)
+    };
+    (id) => {
+        eks::Id
+    };
+    ($id:ident $(<$opt:ident>)?) => {
+        $id $(<$opt>)?
+    };
+    ($($rest:tt)*) => {
+        eks::component_set!(@ [] $($rest)*)
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! filtered {
+    ([$method:ident $par_method:ident] [$($id:ident $(<$opt:ident>)?),*] [$($filter:ty,)*] $f:ident<$c:ident>, $($rest:tt)*) => {
+        eks::filtered!([$method $par_method] [$($id $(<$opt>)?),*] [$($filter,)* $f<$c>,] $($rest)*)
+    };
+    ([$method:ident $par_method:ident] [$($id:ident $(<$opt:ident>)?),*] [$($filter:ty,)*] $f:ident<$c:ident> in $($rest:tt)*) => {
+        eks::filtered!([$method $par_method] [$($id $(<$opt>)?),*] [$($filter,)* $f<$c>,] in $($rest)*)
+    };
+    ([$method:ident $par_method:ident] [$($id:ident $(<$opt:ident>)?),*] [$($filter:ty,)*] !$c:ident, $($rest:tt)*) => {
+        eks::filtered!([$method $par_method] [$($id $(<$opt>)?),*] [$($filter,)* eks::Without<$c>,] $($rest)*)
+    };
+    ([$method:ident $par_method:ident] [$($id:ident $(<$opt:ident>)?),*] [$($filter:ty,)*] !$c:ident in $($rest:tt)*) => {
+        eks::filtered!([$method $par_method] [$($id $(<$opt>)?),*] [$($filter,)* eks::Without<$c>,] in $($rest)*)
+    };
+    ([$method:ident $par_method:ident] [$($id:ident $(<$opt:ident>)?),*] [$($filter:ty,)*] in par $world:expr) => {
+        eks::require_rayon!($world.$par_method::<eks::component_set!($($id $(<$opt>)?),*), ($($filter,)*)>())
+    };
+    ([$method:ident $par_method:ident] [$($id:ident $(<$opt:ident>)?),*] [$($filter:ty,)*] in $world:expr) => {
+        $world.$method::<eks::component_set!($($id $(<$opt>)?),*), ($($filter,)*)>()
+    };
+}
+
 /**
 Macro for immutably accessing components
 
@@ -20,12 +66,29 @@ Creates an iterator over the given `World` where the elements
 are tuples of immutable references to the specified components
 from `Entity`s that have them.
 
+# Filtered syntax `map!(C1, C2, ...; F1, F2, ... in WORLD)`
+
+Like the iterator syntax, but only for `Entity`s that match all of
+the filters, i.e. `Added<C>`, `Changed<C>`, `Related<R>` or `!C` for `Entity`s without `C`.
+`Related<R>` matches the sources of any `R`, not of a specific target.
+
 # Closure syntax `map!(C1, C2, ...)`
 
-Creates a closure that takes an `&Entity` and returns an
-optional tuple of immutable references to the specified components
+Creates a closure that takes an `&Entity`, an `&EntityRef`, an `&EntityRefMut`,
+an `&EntityMut` or an `&EntityView` and returns an optional tuple of immutable references to the specified components
 if the `Entity` has all of them.
 
+# Optional components
+
+Any component can be wrapped in an `Option`, i.e. `map!(Position, Option<Tint> in world)`.
+`Entity`s without it are still included and yield `None` in its place.
+
+# Ids
+
+`id` can be specified in place of a component, i.e. `map!(id, Position in world)`,
+to also yield the `Id` of each `Entity`. The closure syntax returns `None`
+for `Entity`s that are not in a `World`.
+
 # Note
 
 If only one component is specified, the iterator element / optional
@@ -33,22 +96,17 @@ return value will not be a tuple.
 */
 #[macro_export]
 macro_rules! map {
-    ($($id:ident),* in $world:expr) => {
-        $world.iter().filter_map(map!($($id),*))
+    ($($id:ident $(<$opt:ident>)?),* in $world:expr) => {
+        $world.query::<eks::component_set!($($id $(<$opt>)?),*)>()
     };
-    ($($id:ident),* in par $world:expr) => {
-        eks::require_rayon!(
-            rayon::iter::IntoParallelRefIterator::par_iter(&$world)
-                .map(|(_, entity)| entity)
-                .filter_map(map!($($id),*))
-        )
+    ($($id:ident $(<$opt:ident>)?),* in par $world:expr) => {
+        eks::require_rayon!($world.par_query::<eks::component_set!($($id $(<$opt>)?),*)>())
     };
-    ($($id:ident),*) => {
-        |entity| if $(<$id as eks::Component>::try_entity(entity).is_some() &&)* true {
-            Some(($(<$id as eks::Component>::try_entity(entity).unwrap()),*))
-        } else {
-            None
-        }
+    ($($id:ident $(<$opt:ident>)?),*; $($rest:tt)*) => {
+        eks::filtered!([query_filtered par_query_filtered] [$($id $(<$opt>)?),*] [] $($rest)*)
+    };
+    ($($id:ident $(<$opt:ident>)?),*) => {
+        |entity| eks::AsRow::fetch::<eks::component_set!($($id $(<$opt>)?),*)>(entity)
     };
 }
 
@@ -63,107 +121,59 @@ Creates an iterator over the given `World` where the elements
 are tuples of mutable references to the specified components
 from `Entity`s that have them.
 
-# Closure syntax `map_mut!(C1, C2, ...)`
-
-Creates a closure that takes an `&Entity` and returns an
-optional tuple of mutable references to the specified components
-if the `Entity` has all of them.
-
-# Note
-
-If only one component is specified, the iterator element / optional
-return value will not be a tuple.
+# Filtered syntax `map_mut!(C1, C2, ...; F1, F2, ... in WORLD)`
 
-# Warning
-
-It is considered undefined behavior to specify multiples
-of the same component, i.e. `map_mut!(Foo, Foo)`.
-While this violates Rust's borrowing rules, it will still
-compile and run for reasons having to do with performance. If
-you want runtime checks that no two components are the same, use
-`map_mut_checked!`.
-*/
-#[macro_export]
-macro_rules! map_mut {
-    ($($id:ident),* in $world:expr) => {
-        $world.iter_mut().filter_map(map_mut!($($id),*))
-    };
-    ($($id:ident),* in par $world:expr) => {
-        eks::require_rayon!(
-            rayon::iter::IntoParallelRefMutIterator::par_iter_mut(&mut $world)
-                .map(|(_, entity)| entity)
-                .filter_map(map_mut!($($id),*))
-        )
-    };
-    ($($id:ident),*) => {
-        |entity| if $(<$id as eks::Component>::try_entity_mut(entity).is_some() &&)* true {
-            Some(($(<$id as eks::Component>::try_entity_mut(entity).unwrap()),*))
-        } else {
-            None
-        }
-    };
-}
+Like the iterator syntax, but only for `Entity`s that match all of
+the filters, i.e. `Added<C>`, `Changed<C>`, `Related<R>` or `!C` for `Entity`s without `C`.
+`Related<R>` matches the sources of any `R`, not of a specific target.
 
-/**
-Macro for mutably accessing components
+# Closure syntax `map_mut!(C1, C2, ...)`
 
-This macro has two syntaxes:
+Creates a closure that takes an `&mut Entity`, an `&mut EntityRefMut`,
+an `&mut EntityMut` or an `&mut EntityView` and returns an optional tuple of mutable references to the specified components
+if the `Entity` has all of them.
 
-# Iterator syntax `map_mut_checked!(C1, C2, ... in WORLD)`
+# Optional components
 
-Creates an iterator over the given `World` where the elements
-are tuples of mutable references to the specified components
-from `Entity`s that have them.
+Any component can be wrapped in an `Option`, i.e. `map_mut!(Position, Option<Tint> in world)`.
+`Entity`s without it are still included and yield `None` in its place.
 
-# Closure syntax `map_mut_checked!(C1, C2, ...)`
+# Ids
 
-Creates a closure that takes an `&Entity` and returns an
-optional tuple of mutable references to the specified components
-if the `Entity` has all of them.
+`id` can be specified in place of a component, i.e. `map_mut!(id, Position in world)`,
+to also yield the `Id` of each `Entity`. The closure syntax returns `None`
+for `Entity`s that are not in a `World`.
 
-# Notes
+# Note
 
 If only one component is specified, the iterator element / optional
 return value will not be a tuple.
 
-Because the generated closure performs a uniqueness check,
-it will likely be considerably slower than one generated by `map_mut!`.
-
-# Panics
-
-Panics if any two specified components are the same,
-i.e. `map_mut_checked!(Foo, Foo)`.
+Specifying the same component more than once, i.e. `map_mut!(Foo, Foo)`,
+fails to compile:
+```compile_fail
+use eks::*;
+component! { Foo: u8 }
+let mut world = World::new();
+world.insert(entity! { Foo: 1 });
+for (a, b) in map_mut!(Foo, Foo in world) {
+    *a += *b;
+}
+```
 */
 #[macro_export]
-macro_rules! map_mut_checked {
-    ($($id:ident),* in $world:expr) => {
-        $world.iter_mut().filter_map(map_mut_checked!($($id),*))
+macro_rules! map_mut {
+    ($($id:ident $(<$opt:ident>)?),* in $world:expr) => {
+        $world.query_mut::<eks::component_set!($($id $(<$opt>)?),*)>()
     };
-    ($($id:ident),* in par $world:expr) => {
-        eks::require_rayon!(
-            rayon::iter::IntoParallelRefMutIterator::par_iter_mut(&mut $world)
-                .map(|(_, entity)| entity)
-                .filter_map(map_mut_checked!($($id),*))
-        )
+    ($($id:ident $(<$opt:ident>)?),* in par $world:expr) => {
+        eks::require_rayon!($world.par_query_mut::<eks::component_set!($($id $(<$opt>)?),*)>())
     };
-    ($($id:ident),*) => {
-        |entity| {
-            use std::collections::HashSet;
-            let mut used: HashSet<&'static str> = HashSet::new();
-            $(
-                let s = stringify!($id);
-                if !used.contains(&s) {
-                    used.insert(s);
-                } else {
-                    panic!("{:?} is used twice in `map_mut_checked` in {} on line {}:{}", s, file!(), line!(), column!());
-                }
-            )*
-            if $(<$id as eks::Component>::try_entity_mut(entity).is_some() &&)* true {
-                Some(($(<$id as eks::Component>::try_entity_mut(entity).unwrap()),*))
-            } else {
-                None
-            }
-        }
+    ($($id:ident $(<$opt:ident>)?),*; $($rest:tt)*) => {
+        eks::filtered!([query_mut_filtered par_query_mut_filtered] [$($id $(<$opt>)?),*] [] $($rest)*)
+    };
+    ($($id:ident $(<$opt:ident>)?),*) => {
+        |entity| eks::AsRowMut::fetch_mut::<eks::component_set!($($id $(<$opt>)?),*)>(entity)
     };
 }
 
@@ -175,27 +185,35 @@ This macro has two syntaxes:
 # Iterator syntax `tags!(C1, C2, ... in WORLD)`
 
 Creates an iterator over the given `World` where the elements
-are immutable references to `Entity`s that have all of the
-specified components.
+are `EntityRef`s of the `Entity`s that have all of the
+specified components. On a `WorldView`, the elements are the
+`Id`s of the `Entity`s instead, because an `EntityRef` could read
+components that the system has not declared.
+
+# Filtered syntax `tags!(C1, C2, ...; F1, F2, ... in WORLD)`
+
+Like the iterator syntax, but only for `Entity`s that match all of
+the filters, i.e. `Added<C>`, `Changed<C>`, `Related<R>` or `!C` for `Entity`s without `C`.
+`Related<R>` matches the sources of any `R`, not of a specific target.
 
 # Closure syntax `tags!(C1, C2, ...)`
 
-Creates a closure that takes an `&Entity` and returns a `bool`
+Creates a closure that takes an `&Entity`, an `&EntityRef`, an `&EntityRefMut`,
+an `&EntityMut` or an `&EntityView` and returns a `bool`
 indicating whether or not it has all the specified components.
 */
 #[macro_export]
 macro_rules! tags {
     ($($id:ident),* in $world:expr) => {
-        $world.iter().filter(tags!($($id),*))
+        $world.iter_with::<eks::component_set!($($id),*)>()
     };
     ($($id:ident),* in par $world:expr) => {
-        eks::require_rayon!(
-            rayon::iter::IntoParallelRefIterator::par_iter(&$world)
-                .map(|(_, entity)| entity)
-                .filter(tags!($($id),*))
-        )
+        eks::require_rayon!($world.par_iter_with::<eks::component_set!($($id),*)>())
+    };
+    ($($id:ident),*; $($rest:tt)*) => {
+        eks::filtered!([iter_with_filtered par_iter_with_filtered] [$($id),*] [] $($rest)*)
     };
     ($($id:ident),*) => {
-        |entity| $(<$id as eks::Component>::try_entity(entity).is_some() &&)* true
+        |entity| eks::AsRow::fetch::<eks::component_set!($($id),*)>(entity).is_some()
     };
 }
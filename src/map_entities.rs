@@ -0,0 +1,146 @@
+use std::fmt;
+
+use crate::{Entity, Id, IdStatus};
+
+/**
+Trait for values that refer to other `Entity`s by their `Id`s
+
+It is implemented for `Id`, for `Entity`s, and for `Option`s and `Vec`s
+of values that implement it. Implement it for the types of components that
+hold `Id`s in other ways. The `component!` macro implements it for the component
+enum, by calling it on the values of the components whose types implement it.
+That lets `World::dangling_references` find the `Id`s that no longer refer to
+an `Entity` in the `World`, and lets a `Scene` point the `Id`s in its components
+at the `Entity`s it creates.
+
+# Example
+```
+use eks::*;
+
+pub struct Members {
+    pub leader: Id,
+    pub followers: Vec<Id>,
+}
+
+impl MapEntities for Members {
+    fn for_each_entity(&self, f: &mut dyn FnMut(Id)) {
+        self.leader.for_each_entity(f);
+        self.followers.for_each_entity(f);
+    }
+    fn map_entities(&mut self, map: &mut dyn FnMut(Id) -> Id) {
+        self.leader.map_entities(map);
+        self.followers.map_entities(map);
+    }
+}
+
+component! { Health: u8, Target: Id, Squad: Members }
+
+let mut world = World::new();
+let enemy = world.insert(entity! { Health: 3 });
+let hunter = world.insert(entity! { Target: enemy });
+let leader = world.insert(entity! { Health: 5 });
+world.insert(entity! { Squad: Members { leader, followers: vec![hunter] } });
+world.remove(enemy);
+
+let dangling = world.dangling_references();
+assert_eq!(1, dangling.len());
+assert_eq!(hunter, dangling[0].source);
+assert_eq!("Target", dangling[0].component);
+assert_eq!(IdStatus::Removed, dangling[0].status);
+```
+*/
+pub trait MapEntities {
+    /// Call `f` with every `Id` in the value
+    fn for_each_entity(&self, f: &mut dyn FnMut(Id));
+    /// Replace every `Id` in the value with the result of `map`
+    fn map_entities(&mut self, map: &mut dyn FnMut(Id) -> Id);
+}
+
+impl MapEntities for Id {
+    fn for_each_entity(&self, f: &mut dyn FnMut(Id)) {
+        f(*self)
+    }
+    fn map_entities(&mut self, map: &mut dyn FnMut(Id) -> Id) {
+        *self = map(*self)
+    }
+}
+
+impl<T> MapEntities for Option<T>
+where
+    T: MapEntities,
+{
+    fn for_each_entity(&self, f: &mut dyn FnMut(Id)) {
+        if let Some(value) = self {
+            value.for_each_entity(f)
+        }
+    }
+    fn map_entities(&mut self, map: &mut dyn FnMut(Id) -> Id) {
+        if let Some(value) = self {
+            value.map_entities(map)
+        }
+    }
+}
+
+impl<T> MapEntities for Vec<T>
+where
+    T: MapEntities,
+{
+    fn for_each_entity(&self, f: &mut dyn FnMut(Id)) {
+        for value in self {
+            value.for_each_entity(f)
+        }
+    }
+    fn map_entities(&mut self, map: &mut dyn FnMut(Id) -> Id) {
+        for value in self {
+            value.map_entities(map)
+        }
+    }
+}
+
+/// The `Id`s in all of the `Entity`'s components
+impl<C> MapEntities for Entity<C>
+where
+    C: MapEntities,
+{
+    fn for_each_entity(&self, f: &mut dyn FnMut(Id)) {
+        for (_, slot) in self.components.iter().chain(&self.sparse) {
+            slot.get().for_each_entity(f)
+        }
+    }
+    fn map_entities(&mut self, map: &mut dyn FnMut(Id) -> Id) {
+        for (_, slot) in self.components.iter_mut().chain(&mut self.sparse) {
+            slot.value.get_mut().map_entities(map)
+        }
+    }
+}
+
+/// A component that refers to an `Id` that is not in the `World`,
+/// as found by `World::dangling_references`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DanglingReference {
+    /// The `Id` of the `Entity` that has the component
+    pub source: Id,
+    /// The name of the component
+    pub component: &'static str,
+    /// The `Id` that the component refers to
+    pub target: Id,
+    /// Why the target is not in the `World`
+    pub status: IdStatus,
+}
+
+impl fmt::Display for DanglingReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of entity {} refers to entity {}, which {}",
+            self.component,
+            self.source,
+            self.target,
+            match self.status {
+                IdStatus::Alive => "is in the world",
+                IdStatus::Removed => "is stale: its index has a newer generation",
+                IdStatus::NeverExisted => "was not handed out by the world",
+            }
+        )
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+
+use crate::{Commands, Id, World};
+
+/// An observer that takes the event as `Any`
+pub(crate) type Observer<C> =
+    Arc<dyn Fn(Id, &dyn Any, &mut World<C>, &mut Commands<C>) + Send + Sync>;
+
+/// The observers of a single event type
+struct EventObservers<C> {
+    /// The observers of every target
+    global: Vec<Observer<C>>,
+    /// The observers of a single target
+    entities: HashMap<Id, Vec<Observer<C>>>,
+}
+
+/// The observers registered with a `World`
+pub(crate) struct Observers<C> {
+    observers: HashMap<TypeId, EventObservers<C>>,
+}
+
+impl<C> Default for Observers<C> {
+    fn default() -> Self {
+        Observers {
+            observers: HashMap::new(),
+        }
+    }
+}
+
+impl<C> Observers<C> {
+    /// Add an observer of the given event type, for a single target if one is given
+    pub(crate) fn insert(&mut self, event: TypeId, target: Option<Id>, observer: Observer<C>) {
+        let observers = self
+            .observers
+            .entry(event)
+            .or_insert_with(|| EventObservers {
+                global: Vec::new(),
+                entities: HashMap::new(),
+            });
+        match target {
+            Some(target) => observers.entities.entry(target).or_default().push(observer),
+            None => observers.global.push(observer),
+        }
+    }
+    /**
+    Get the observers of the given event type that watch the target,
+    starting with the ones that only watch the target
+
+    The observers are cloned so that they can be run while the `World` is changed.
+    */
+    pub(crate) fn get(&self, event: TypeId, target: Id) -> Vec<Observer<C>> {
+        let observers = match self.observers.get(&event) {
+            Some(observers) => observers,
+            None => return Vec::new(),
+        };
+        observers
+            .entities
+            .get(&target)
+            .into_iter()
+            .flatten()
+            .chain(&observers.global)
+            .cloned()
+            .collect()
+    }
+    /// Remove the observers that only watch an `Entity` that is removed from the `World`
+    pub(crate) fn remove_entity(&mut self, id: Id) {
+        for observers in self.observers.values_mut() {
+            observers.entities.remove(&id);
+        }
+    }
+}
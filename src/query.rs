@@ -0,0 +1,688 @@
+use std::{any::type_name, fmt, marker::PhantomData};
+
+#[cfg(feature = "f_rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    Access, ColumnRef, Component, EntityRef, Filter, Id, Row, Storage, Table, World, WorldView,
+};
+
+/**
+Trait for a set of components that can be borrowed from an `Entity` at once
+
+This is implemented for every `Component`, for `Option`s of them, which borrow
+the component if the `Entity` has it, for `Id`, which yields the `Id` of the `Entity`
+without borrowing anything, for `Read` and `Write`, and for tuples of up to 12 `Member`s.
+
+Borrowing is done in two steps so that a `World` only has to look up
+where the components are once for each group of `Entity`s with the same
+components, rather than once for every `Entity`.
+
+# Safety
+
+`fetch_unchecked` must only borrow the slots of the components in the set,
+must borrow each of them at most once, and must only borrow the slots
+of the components in `writes` mutably.
+*/
+pub unsafe trait ComponentSet<'a, C> {
+    /// The references to the components' values
+    type Ref;
+    /// The mutable references to the components' values
+    type Mut;
+    /// Where the components are in a `Table`
+    type Columns: Copy + Send + Sync;
+    /// Fails to evaluate if the set contains the same component more than once
+    #[doc(hidden)]
+    const DISTINCT: () = ();
+    /// The name of a component in the set with `Storage::Sparse`, if there is one
+    const SPARSE: Option<&'static str>;
+    /// Get the names of the components in the set
+    fn names() -> Vec<&'static str>;
+    /// Get the names of the components that `fetch_unchecked` borrows mutably
+    fn writes() -> Vec<&'static str> {
+        Self::names()
+    }
+    /// Find where the components are in a `Table`
+    ///
+    /// Returns `None` if no `Entity` in the table has all of them.
+    fn columns(table: Table<'a, C>) -> Option<Self::Columns>;
+    /// Get references to the components' values
+    fn fetch(row: Row<'a, C>, columns: Self::Columns) -> Option<Self::Ref>;
+    /**
+    Get mutable references to the components' values through a shared reference
+    and mark them as changed at `tick`
+
+    # Safety
+
+    The caller must have exclusive access to the `Entity`'s components in the set,
+    and the set must not contain the same component more than once.
+    */
+    unsafe fn fetch_unchecked(
+        row: Row<'a, C>,
+        columns: Self::Columns,
+        tick: u64,
+    ) -> Option<Self::Mut>;
+}
+
+unsafe impl<'a, T> ComponentSet<'a, T::Enum> for T
+where
+    T: Component,
+    T::Type: 'a,
+    T::Enum: 'a,
+{
+    type Ref = &'a T::Type;
+    type Mut = &'a mut T::Type;
+    type Columns = ColumnRef<'a, T>;
+    const SPARSE: Option<&'static str> = match T::STORAGE {
+        Storage::Table => None,
+        Storage::Sparse => Some(T::AS_STR),
+    };
+    fn names() -> Vec<&'static str> {
+        vec![T::AS_STR]
+    }
+    fn columns(table: Table<'a, T::Enum>) -> Option<Self::Columns> {
+        table.column::<T>()
+    }
+    fn fetch(row: Row<'a, T::Enum>, column: ColumnRef<'a, T>) -> Option<Self::Ref> {
+        column.get(row)
+    }
+    unsafe fn fetch_unchecked(
+        row: Row<'a, T::Enum>,
+        column: ColumnRef<'a, T>,
+        tick: u64,
+    ) -> Option<Self::Mut> {
+        // Safety: guaranteed by the caller
+        unsafe { column.get_unchecked_mut(row, tick) }
+    }
+}
+
+unsafe impl<'a, T> ComponentSet<'a, T::Enum> for Option<T>
+where
+    T: Component,
+    T::Type: 'a,
+    T::Enum: 'a,
+{
+    type Ref = Option<&'a T::Type>;
+    type Mut = Option<&'a mut T::Type>;
+    /// Where the component is, if any `Entity` in the table has it
+    type Columns = Option<ColumnRef<'a, T>>;
+    const SPARSE: Option<&'static str> = None;
+    fn names() -> Vec<&'static str> {
+        vec![T::AS_STR]
+    }
+    fn columns(table: Table<'a, T::Enum>) -> Option<Self::Columns> {
+        Some(table.column::<T>())
+    }
+    fn fetch(row: Row<'a, T::Enum>, column: Self::Columns) -> Option<Self::Ref> {
+        Some(column.and_then(|column| column.get(row)))
+    }
+    unsafe fn fetch_unchecked(
+        row: Row<'a, T::Enum>,
+        column: Self::Columns,
+        tick: u64,
+    ) -> Option<Self::Mut> {
+        // Safety: guaranteed by the caller
+        Some(column.and_then(|column| unsafe { column.get_unchecked_mut(row, tick) }))
+    }
+}
+
+unsafe impl<'a, C> ComponentSet<'a, C> for Id
+where
+    C: 'a,
+{
+    type Ref = Id;
+    type Mut = Id;
+    type Columns = ();
+    const SPARSE: Option<&'static str> = None;
+    fn names() -> Vec<&'static str> {
+        Vec::new()
+    }
+    fn columns(_: Table<'a, C>) -> Option<Self::Columns> {
+        Some(())
+    }
+    /// Returns `None` if the `Entity` is not in a `World`
+    fn fetch(row: Row<'a, C>, _: ()) -> Option<Id> {
+        row.id()
+    }
+    unsafe fn fetch_unchecked(row: Row<'a, C>, _: (), _: u64) -> Option<Id> {
+        row.id()
+    }
+}
+
+/**
+A component that is only read, even when the query is mutable
+
+Mostly useful with `Query`, where it lets the `Access` of the query tell
+the components it reads apart from the ones it writes.
+*/
+pub struct Read<T>(PhantomData<T>);
+
+/// A component that is written, same as using the `Component` itself
+pub struct Write<T>(PhantomData<T>);
+
+unsafe impl<'a, T> ComponentSet<'a, T::Enum> for Read<T>
+where
+    T: Component,
+    T::Type: 'a,
+    T::Enum: 'a,
+{
+    type Ref = &'a T::Type;
+    type Mut = &'a T::Type;
+    type Columns = ColumnRef<'a, T>;
+    const SPARSE: Option<&'static str> = <T as ComponentSet<'a, T::Enum>>::SPARSE;
+    fn names() -> Vec<&'static str> {
+        vec![T::AS_STR]
+    }
+    fn writes() -> Vec<&'static str> {
+        Vec::new()
+    }
+    fn columns(table: Table<'a, T::Enum>) -> Option<Self::Columns> {
+        <T as ComponentSet<'a, T::Enum>>::columns(table)
+    }
+    fn fetch(row: Row<'a, T::Enum>, column: ColumnRef<'a, T>) -> Option<Self::Ref> {
+        <T as ComponentSet<'a, T::Enum>>::fetch(row, column)
+    }
+    unsafe fn fetch_unchecked(
+        row: Row<'a, T::Enum>,
+        column: ColumnRef<'a, T>,
+        _: u64,
+    ) -> Option<Self::Mut> {
+        <T as ComponentSet<'a, T::Enum>>::fetch(row, column)
+    }
+}
+
+unsafe impl<'a, T> ComponentSet<'a, T::Enum> for Write<T>
+where
+    T: Component,
+    T::Type: 'a,
+    T::Enum: 'a,
+{
+    type Ref = &'a T::Type;
+    type Mut = &'a mut T::Type;
+    type Columns = ColumnRef<'a, T>;
+    const SPARSE: Option<&'static str> = <T as ComponentSet<'a, T::Enum>>::SPARSE;
+    fn names() -> Vec<&'static str> {
+        vec![T::AS_STR]
+    }
+    fn columns(table: Table<'a, T::Enum>) -> Option<Self::Columns> {
+        <T as ComponentSet<'a, T::Enum>>::columns(table)
+    }
+    fn fetch(row: Row<'a, T::Enum>, column: ColumnRef<'a, T>) -> Option<Self::Ref> {
+        <T as ComponentSet<'a, T::Enum>>::fetch(row, column)
+    }
+    unsafe fn fetch_unchecked(
+        row: Row<'a, T::Enum>,
+        column: ColumnRef<'a, T>,
+        tick: u64,
+    ) -> Option<Self::Mut> {
+        <T as ComponentSet<'a, T::Enum>>::fetch_unchecked(row, column, tick)
+    }
+}
+
+/**
+Trait for the members of a tuple `ComponentSet`
+
+Each member borrows at most one component, which lets a tuple
+check at compile time that it doesn't borrow any component twice.
+
+# Safety
+
+`NAME` must be the name of the component the member borrows
+*/
+pub unsafe trait Member<'a, C>: ComponentSet<'a, C> {
+    /// The name of the component the member borrows, if it borrows one
+    const NAME: Option<&'static str>;
+}
+
+unsafe impl<'a, T> Member<'a, T::Enum> for T
+where
+    T: Component,
+    T::Type: 'a,
+    T::Enum: 'a,
+{
+    const NAME: Option<&'static str> = Some(T::AS_STR);
+}
+
+unsafe impl<'a, T> Member<'a, T::Enum> for Option<T>
+where
+    T: Component,
+    T::Type: 'a,
+    T::Enum: 'a,
+{
+    const NAME: Option<&'static str> = Some(T::AS_STR);
+}
+
+unsafe impl<'a, T> Member<'a, T::Enum> for Read<T>
+where
+    T: Component,
+    T::Type: 'a,
+    T::Enum: 'a,
+{
+    const NAME: Option<&'static str> = Some(T::AS_STR);
+}
+
+unsafe impl<'a, T> Member<'a, T::Enum> for Write<T>
+where
+    T: Component,
+    T::Type: 'a,
+    T::Enum: 'a,
+{
+    const NAME: Option<&'static str> = Some(T::AS_STR);
+}
+
+unsafe impl<'a, C> Member<'a, C> for Id
+where
+    C: 'a,
+{
+    const NAME: Option<&'static str> = None;
+}
+
+/// Gets the first name that is not `None`
+const fn first_sparse(names: &[Option<&'static str>]) -> Option<&'static str> {
+    let mut i = 0;
+    while i < names.len() {
+        if names[i].is_some() {
+            return names[i];
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Checks at compile time that no two component names are the same
+const fn distinct_names(names: &[Option<&str>]) -> bool {
+    let mut i = 0;
+    while i < names.len() {
+        let mut j = i + 1;
+        while j < names.len() {
+            if let (Some(a), Some(b)) = (names[i], names[j]) {
+                if str_eq(a, b) {
+                    return false;
+                }
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+macro_rules! component_set_tuple {
+    ($($t:ident),*) => {
+        unsafe impl<'a, C, $($t),*> ComponentSet<'a, C> for ($($t,)*)
+        where
+            C: 'a,
+            $($t: Member<'a, C>,)*
+        {
+            type Ref = ($($t::Ref,)*);
+            type Mut = ($($t::Mut,)*);
+            type Columns = ($($t::Columns,)*);
+            const DISTINCT: () = assert!(
+                distinct_names(&[$($t::NAME),*]),
+                "The same component cannot be borrowed mutably more than once"
+            );
+            const SPARSE: Option<&'static str> = first_sparse(&[$($t::SPARSE),*]);
+            fn names() -> Vec<&'static str> {
+                let mut names = Vec::new();
+                $(names.extend($t::names());)*
+                names
+            }
+            fn writes() -> Vec<&'static str> {
+                let mut names = Vec::new();
+                $(names.extend($t::writes());)*
+                names
+            }
+            fn columns(table: Table<'a, C>) -> Option<Self::Columns> {
+                Some(($($t::columns(table)?,)*))
+            }
+            #[allow(non_snake_case)]
+            fn fetch(row: Row<'a, C>, columns: Self::Columns) -> Option<Self::Ref> {
+                let ($($t,)*) = columns;
+                Some(($($t::fetch(row, $t)?,)*))
+            }
+            #[allow(non_snake_case)]
+            unsafe fn fetch_unchecked(
+                row: Row<'a, C>,
+                columns: Self::Columns,
+                tick: u64,
+            ) -> Option<Self::Mut> {
+                let ($($t,)*) = columns;
+                Some(($($t::fetch_unchecked(row, $t, tick)?,)*))
+            }
+        }
+    };
+}
+
+component_set_tuple!(A);
+component_set_tuple!(A, B);
+component_set_tuple!(A, B, D);
+component_set_tuple!(A, B, D, E);
+component_set_tuple!(A, B, D, E, F);
+component_set_tuple!(A, B, D, E, F, G);
+component_set_tuple!(A, B, D, E, F, G, H);
+component_set_tuple!(A, B, D, E, F, G, H, I);
+component_set_tuple!(A, B, D, E, F, G, H, I, J);
+component_set_tuple!(A, B, D, E, F, G, H, I, J, K);
+component_set_tuple!(A, B, D, E, F, G, H, I, J, K, L);
+component_set_tuple!(A, B, D, E, F, G, H, I, J, K, L, M);
+
+/**
+A typed query that remembers which archetypes have the components it borrows
+
+`Q` is a `ComponentSet`, usually a tuple of `Read`s and `Write`s,
+and `F` is a `Filter`. `map!`, `map_mut!` and `tags!` look up the matching
+archetypes every time they are used, while a `Query` looks them up once
+and afterwards only checks the archetypes that were created since,
+so it pays to keep one around when the same query is run every frame.
+
+A `Query` forgets the archetypes it found when it is used with a different `World`.
+
+# Example
+```
+use eks::*;
+
+component! { Position: isize, Speed: isize }
+
+let mut world = World::new();
+world.insert(entity! { Position: 0, Speed: 1 });
+world.insert(entity! { Position: 0 });
+
+let mut movement = Query::<(Write<Position>, Read<Speed>)>::new();
+for _ in 0..3 {
+    for (position, speed) in movement.iter_mut(&mut world) {
+        *position += *speed;
+    }
+}
+
+let mut positions = map!(Position in world).copied().collect::<Vec<_>>();
+positions.sort();
+assert_eq!(vec![0, 3], positions);
+assert_eq!(Access::new().read::<Speed>().write::<Position>(), movement.access());
+```
+*/
+pub struct Query<Q, F = ()> {
+    /// The `World` that the archetypes belong to
+    world: Option<u64>,
+    /// The number of archetypes that have been checked
+    checked: usize,
+    /// The indices of the archetypes that match
+    archetypes: Vec<usize>,
+    marker: PhantomData<fn() -> (Q, F)>,
+}
+
+impl<Q, F> Default for Query<Q, F> {
+    fn default() -> Self {
+        Query::new()
+    }
+}
+
+impl<Q, F> Clone for Query<Q, F> {
+    fn clone(&self) -> Self {
+        Query {
+            world: self.world,
+            checked: self.checked,
+            archetypes: self.archetypes.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<Q, F> fmt::Debug for Query<Q, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Query")
+            .field("query", &type_name::<Q>())
+            .field("filter", &type_name::<F>())
+            .field("archetypes", &self.archetypes)
+            .finish()
+    }
+}
+
+impl<Q, F> Query<Q, F> {
+    /// Create a new `Query` that hasn't looked at any archetypes yet
+    pub fn new() -> Query<Q, F> {
+        Query {
+            world: None,
+            checked: 0,
+            archetypes: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+    /**
+    Get the `Access` that running the query mutably needs
+
+    Components in `Q` are written unless they are `Read`,
+    and the components the filter looks at are read.
+    */
+    pub fn access<C>(&self) -> Access
+    where
+        Q: ComponentSet<'static, C>,
+        F: Filter<C>,
+    {
+        let writes = Q::writes();
+        let mut access = Access::new();
+        for name in Q::names().into_iter().chain(F::names()) {
+            if !writes.contains(&name) {
+                access = access.read_name(name);
+            }
+        }
+        for name in writes {
+            access = access.write_name(name);
+        }
+        access
+    }
+    /// Check the archetypes that were created since the last time
+    /// and get all the ones that match
+    fn update<'a, C>(&'a mut self, world: &'a World<C>) -> &'a [usize]
+    where
+        Q: ComponentSet<'a, C>,
+        F: Filter<C>,
+    {
+        if self.world != Some(world.world_id()) {
+            self.world = Some(world.world_id());
+            self.checked = 0;
+            self.archetypes.clear();
+        }
+        world.match_archetypes::<Q, F>(self.checked, &mut self.archetypes);
+        self.checked = world.archetype_count();
+        &self.archetypes
+    }
+    /// Same as `World::query_filtered`
+    pub fn iter<'a, C>(&'a mut self, world: &'a World<C>) -> impl Iterator<Item = Q::Ref> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        let last_run = world.last_change_tick();
+        world.query_since::<Q, F>(Some(self.update(world)), last_run)
+    }
+    /// Same as `World::query_mut_filtered`
+    pub fn iter_mut<'a, C>(
+        &'a mut self,
+        world: &'a mut World<C>,
+    ) -> impl Iterator<Item = Q::Mut> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Q::DISTINCT;
+        let world = &*world;
+        let last_run = world.last_change_tick();
+        // Safety: the world is borrowed mutably, and `DISTINCT`
+        // guarantees that the components are all different
+        unsafe { world.query_unchecked::<Q, F>(Some(self.update(world)), last_run) }
+    }
+    /// Same as `World::iter_with_filtered`
+    pub fn entities<'a, C>(
+        &'a mut self,
+        world: &'a World<C>,
+    ) -> impl Iterator<Item = EntityRef<'a, C>> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        let last_run = world.last_change_tick();
+        world.iter_with_since::<Q, F>(Some(self.update(world)), last_run)
+    }
+    /**
+    Same as `WorldView::query_filtered`
+
+    # Panics
+
+    Panics if any of the components are not declared as read or written
+    */
+    pub fn iter_view<'a, C>(
+        &'a mut self,
+        view: &'a WorldView<'_, C>,
+    ) -> impl Iterator<Item = Q::Ref> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        view.check::<F>(&Q::names(), &[]);
+        let world = view.world();
+        world.query_since::<Q, F>(Some(self.update(world)), view.last_run())
+    }
+    /**
+    Same as `WorldView::query_mut_filtered`
+
+    # Panics
+
+    Panics if any of the components are not declared as written, except for
+    `Read` ones, or if any of the `Read` or the filter's components are not
+    declared as read or written
+    */
+    pub fn iter_view_mut<'a, C>(
+        &'a mut self,
+        view: &'a mut WorldView<'_, C>,
+    ) -> impl Iterator<Item = Q::Mut> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Q::DISTINCT;
+        view.check::<F>(&Q::names(), &Q::writes());
+        let world = view.world();
+        // Safety: see `WorldView::query_mut_filtered`
+        unsafe { world.query_unchecked::<Q, F>(Some(self.update(world)), view.last_run()) }
+    }
+    /// Parallel version of `Query::iter`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_iter<'a, C>(
+        &'a mut self,
+        world: &'a World<C>,
+    ) -> impl ParallelIterator<Item = Q::Ref> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Ref: Send,
+        F: Filter<C> + 'a,
+    {
+        let last_run = world.last_change_tick();
+        world.par_query_since::<Q, F>(Some(self.update(world)), last_run)
+    }
+    /// Parallel version of `Query::iter_mut`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_iter_mut<'a, C>(
+        &'a mut self,
+        world: &'a mut World<C>,
+    ) -> impl ParallelIterator<Item = Q::Mut> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Mut: Send,
+        F: Filter<C> + 'a,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Q::DISTINCT;
+        let world = &*world;
+        let last_run = world.last_change_tick();
+        // Safety: see `Query::iter_mut`
+        unsafe { world.par_query_unchecked::<Q, F>(Some(self.update(world)), last_run) }
+    }
+    /// Parallel version of `Query::entities`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_entities<'a, C>(
+        &'a mut self,
+        world: &'a World<C>,
+    ) -> impl ParallelIterator<Item = EntityRef<'a, C>> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        let last_run = world.last_change_tick();
+        world.par_iter_with_since::<Q, F>(Some(self.update(world)), last_run)
+    }
+    /// Parallel version of `Query::iter_view`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_iter_view<'a, C>(
+        &'a mut self,
+        view: &'a WorldView<'_, C>,
+    ) -> impl ParallelIterator<Item = Q::Ref> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Ref: Send,
+        F: Filter<C> + 'a,
+    {
+        view.check::<F>(&Q::names(), &[]);
+        let world = view.world();
+        world.par_query_since::<Q, F>(Some(self.update(world)), view.last_run())
+    }
+    /// Parallel version of `Query::iter_view_mut`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_iter_view_mut<'a, C>(
+        &'a mut self,
+        view: &'a mut WorldView<'_, C>,
+    ) -> impl ParallelIterator<Item = Q::Mut> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Mut: Send,
+        F: Filter<C> + 'a,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Q::DISTINCT;
+        view.check::<F>(&Q::names(), &Q::writes());
+        let world = view.world();
+        // Safety: see `WorldView::query_mut_filtered`
+        unsafe { world.par_query_unchecked::<Q, F>(Some(self.update(world)), view.last_run()) }
+    }
+}
+
+/// An iterator that is one of two iterators
+pub(crate) enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+impl<A, B> Iterator for Either<A, B>
+where
+    A: Iterator,
+    B: Iterator<Item = A::Item>,
+{
+    type Item = A::Item;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Either::Left(iter) => iter.next(),
+            Either::Right(iter) => iter.next(),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::{any::TypeId, collections::HashMap};
+
+use crate::Id;
+
+/**
+Trait for relations between `Entity`s
+
+A relation points from a source `Entity` to a target `Entity`.
+A source can have the same relation with many targets, and a target
+can be pointed at by many sources. Relations are added with `World::relate`,
+and `World::sources` gets the `Entity`s that point at a target.
+
+Relations carry no data and are not components: they are kept by the `World`
+next to its archetypes, keyed by the type that implements `Relation`.
+`map!` and the other queries can't read them, but they can be filtered by
+with `Related<R>`, which only matches the `Entity`s that are the source of an `R`.
+`Related<R>` can't tell targets apart, so `World::sources` is the way to find
+the `Entity`s that point at a specific target.
+
+# Example
+```
+use eks::*;
+
+component! { Name: String }
+
+struct Targeting;
+impl Relation for Targeting {}
+
+struct DockedAt;
+impl Relation for DockedAt {
+    const ON_TARGET_REMOVED: OnTargetRemoved = OnTargetRemoved::Remove;
+}
+
+let mut world = World::new();
+let station = world.insert(entity! { Name: "station".into() });
+let ship = world.insert(entity! { Name: "ship".into() });
+let turret = world.insert(entity! { Name: "turret".into() });
+world.relate::<DockedAt>(ship, station);
+world.relate::<Targeting>(turret, ship);
+
+assert_eq!(&[turret], world.sources::<Targeting>(ship));
+assert_eq!(
+    vec!["turret"],
+    map!(Name; Related<Targeting> in world).collect::<Vec<_>>()
+);
+
+// Removing the station removes the docked ship,
+// which in turn stops the turret from targeting it
+world.remove(station);
+assert!(!world.contains(ship));
+assert!(world.targets::<Targeting>(turret).is_empty());
+```
+*/
+pub trait Relation: 'static {
+    /// What happens to the sources when a target is removed from the `World`
+    const ON_TARGET_REMOVED: OnTargetRemoved = OnTargetRemoved::Unrelate;
+}
+
+/// What happens to the sources of a `Relation` when its target is removed from the `World`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OnTargetRemoved {
+    /// The relations with the target are removed. This is the default.
+    Unrelate,
+    /// The sources are removed from the `World` as well
+    Remove,
+    /// The relations are kept and reported by `World::dangling`
+    Keep,
+}
+
+/// The pairs of a single `Relation` in a `World`, which `Related` looks up once per archetype
+pub struct RelationTable {
+    policy: OnTargetRemoved,
+    /// The targets of each source
+    targets: HashMap<Id, Vec<Id>>,
+    /// The sources of each target
+    sources: HashMap<Id, Vec<Id>>,
+}
+
+/// The relations between the `Entity`s in a `World`, keyed by the `Relation`'s `TypeId`
+#[derive(Default)]
+pub(crate) struct Relations {
+    tables: HashMap<TypeId, RelationTable>,
+}
+
+impl Relations {
+    /// Add a relation and return `false` if it already existed
+    pub(crate) fn insert<R>(&mut self, source: Id, target: Id) -> bool
+    where
+        R: Relation,
+    {
+        let table = self
+            .tables
+            .entry(TypeId::of::<R>())
+            .or_insert_with(|| RelationTable {
+                policy: R::ON_TARGET_REMOVED,
+                targets: HashMap::new(),
+                sources: HashMap::new(),
+            });
+        let targets = table.targets.entry(source).or_default();
+        if targets.contains(&target) {
+            return false;
+        }
+        targets.push(target);
+        table.sources.entry(target).or_default().push(source);
+        true
+    }
+    /// Remove a relation and return `false` if it didn't exist
+    pub(crate) fn remove<R>(&mut self, source: Id, target: Id) -> bool
+    where
+        R: Relation,
+    {
+        match self.tables.get_mut(&TypeId::of::<R>()) {
+            Some(table) => {
+                unlink(&mut table.targets, source, target)
+                    && unlink(&mut table.sources, target, source)
+            }
+            None => false,
+        }
+    }
+    pub(crate) fn targets<R>(&self, source: Id) -> &[Id]
+    where
+        R: Relation,
+    {
+        self.tables
+            .get(&TypeId::of::<R>())
+            .and_then(|table| table.targets.get(&source))
+            .map_or(&[], Vec::as_slice)
+    }
+    pub(crate) fn sources<R>(&self, target: Id) -> &[Id]
+    where
+        R: Relation,
+    {
+        self.tables
+            .get(&TypeId::of::<R>())
+            .and_then(|table| table.sources.get(&target))
+            .map_or(&[], Vec::as_slice)
+    }
+    /// Get the pairs of the relation `R`, if any were ever added
+    pub(crate) fn table<R>(&self) -> Option<&RelationTable>
+    where
+        R: Relation,
+    {
+        self.tables.get(&TypeId::of::<R>())
+    }
+    /// Iterates through the source and target of every relation of type `R`
+    pub(crate) fn pairs<R>(&self) -> impl Iterator<Item = (Id, Id)> + '_
+    where
+        R: Relation,
+    {
+        self.tables
+            .get(&TypeId::of::<R>())
+            .into_iter()
+            .flat_map(|table| table.targets.iter())
+            .flat_map(|(&source, targets)| targets.iter().map(move |&target| (source, target)))
+    }
+    /**
+    Forget the relations of an `Entity` that was removed from the `World`
+
+    Relations that target it are handled according to their `OnTargetRemoved`.
+    Returns the sources that have to be removed from the `World` as well.
+    */
+    pub(crate) fn remove_entity(&mut self, id: Id) -> Vec<Id> {
+        let mut remove = Vec::new();
+        for table in self.tables.values_mut() {
+            for target in table.targets.remove(&id).unwrap_or_default() {
+                unlink(&mut table.sources, target, id);
+            }
+            if table.policy == OnTargetRemoved::Keep {
+                continue;
+            }
+            for source in table.sources.remove(&id).unwrap_or_default() {
+                unlink(&mut table.targets, source, id);
+                if table.policy == OnTargetRemoved::Remove {
+                    remove.push(source);
+                }
+            }
+        }
+        remove
+    }
+}
+
+impl RelationTable {
+    /// Check if the `Entity` with the given `Id` is the source of any of the relations
+    pub(crate) fn has_targets(&self, source: Id) -> bool {
+        self.targets.contains_key(&source)
+    }
+}
+
+/// Remove `value` from the list of `key` and return `false` if it wasn't there
+fn unlink(map: &mut HashMap<Id, Vec<Id>>, key: Id, value: Id) -> bool {
+    let list = match map.get_mut(&key) {
+        Some(list) => list,
+        None => return false,
+    };
+    let len = list.len();
+    list.retain(|&id| id != value);
+    let removed = list.len() != len;
+    if list.is_empty() {
+        map.remove(&key);
+    }
+    removed
+}
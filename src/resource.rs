@@ -0,0 +1,93 @@
+use std::{
+    any::{Any, TypeId},
+    cell::UnsafeCell,
+    collections::HashMap,
+};
+
+/// A resource value stored in a `World`
+struct Resource {
+    value: UnsafeCell<Box<dyn Any + Send + Sync>>,
+}
+
+// Safety: the value is only mutated through `&mut Resource` or by
+// `Resources::get_unchecked_mut`, whose callers have exclusive access
+// to it, and the value itself is `Send + Sync`
+unsafe impl Sync for Resource {}
+
+/// Typed singletons stored in a `World`, one for each type
+#[derive(Default)]
+pub(crate) struct Resources {
+    map: HashMap<TypeId, Resource>,
+}
+
+impl Resources {
+    pub(crate) fn insert<R>(&mut self, value: R) -> Option<R>
+    where
+        R: Send + Sync + 'static,
+    {
+        let old = self.map.insert(
+            TypeId::of::<R>(),
+            Resource {
+                value: UnsafeCell::new(Box::new(value)),
+            },
+        )?;
+        Some(*downcast(old.value.into_inner()))
+    }
+    pub(crate) fn remove<R>(&mut self) -> Option<R>
+    where
+        R: 'static,
+    {
+        let old = self.map.remove(&TypeId::of::<R>())?;
+        Some(*downcast(old.value.into_inner()))
+    }
+    pub(crate) fn contains<R>(&self) -> bool
+    where
+        R: 'static,
+    {
+        self.map.contains_key(&TypeId::of::<R>())
+    }
+    pub(crate) fn get<R>(&self) -> Option<&R>
+    where
+        R: 'static,
+    {
+        let resource = self.map.get(&TypeId::of::<R>())?;
+        // Safety: the value is only mutated with exclusive access,
+        // which cannot overlap with this shared borrow
+        let value = unsafe { &*resource.value.get() };
+        value.downcast_ref()
+    }
+    pub(crate) fn get_mut<R>(&mut self) -> Option<&mut R>
+    where
+        R: 'static,
+    {
+        let resource = self.map.get_mut(&TypeId::of::<R>())?;
+        resource.value.get_mut().downcast_mut()
+    }
+    /**
+    Get a mutable reference to a resource through a shared reference
+
+    # Safety
+
+    The caller must have exclusive access to the resource while the reference is alive
+    */
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_unchecked_mut<R>(&self) -> Option<&mut R>
+    where
+        R: 'static,
+    {
+        let resource = self.map.get(&TypeId::of::<R>())?;
+        // Safety: the caller guarantees exclusive access
+        let value = unsafe { &mut *resource.value.get() };
+        value.downcast_mut()
+    }
+}
+
+fn downcast<R>(value: Box<dyn Any + Send + Sync>) -> Box<R>
+where
+    R: 'static,
+{
+    match value.downcast() {
+        Ok(value) => value,
+        Err(_) => unreachable!("Resources are keyed by their TypeId"),
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+};
+
+#[cfg(feature = "f_serde")]
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "f_uuid")]
+use uuid::Uuid;
+
+#[cfg(feature = "f_serde")]
+use crate::{hierarchy::Hierarchy, serialization::OrderedMap, SerdeComponents};
+use crate::{Commands, Entity, Id, MapEntities, World};
+
+/**
+A set of `Entity`s that can be added to a `World` any number of times
+
+A `Scene` is extracted from a `World` with `Scene::extract`. It keeps
+the `Id`s the `Entity`s had there, and the parents and children among them.
+`Scene::instantiate` adds a copy of every `Entity` to a `World`, and points
+the `Id`s in their components that refer to other `Entity`s of the `Scene`
+at the new copies. `Id`s of `Entity`s outside the `Scene` would be meaningless
+in another `World`, so `Scene::instantiate` refuses to add a `Scene` that has any,
+and `Scene::instantiate_with` asks what to replace them with.
+
+Extracting and instantiating copy the `Entity`s, so the component enum has to
+implement `Clone`. The `Id`s in the components are found through the `MapEntities`
+impls of their types.
+With the `f_serde` feature, `Scene`s can be serialized, so they can be saved to files.
+
+# Example
+```
+use eks::*;
+
+component! { #[derive(Clone)] Name: String, Opens: Id, Level: () }
+
+let mut editor = World::new();
+let key = editor.insert(entity! { Name: "key".into(), Level: () });
+let door = editor.insert(entity! { Name: "door".into(), Opens: key, Level: () });
+editor.insert(entity! { Name: "camera".into() });
+
+let level: Vec<Id> = tags!(Level in editor).ids().collect();
+let scene = Scene::extract(&editor, level);
+assert_eq!(2, scene.len());
+
+let mut world = World::new();
+let first = scene.instantiate(&mut world).unwrap();
+let second = scene.instantiate(&mut world).unwrap();
+assert_eq!(4, world.len());
+
+// Every door opens the key that was created with it
+assert_eq!(first[&key], world[first[&door]][Opens]);
+assert_eq!(second[&key], world[second[&door]][Opens]);
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scene<C> {
+    /// The `Entity`s with the `Id`s they had in the `World` they were extracted from
+    entities: Vec<(Id, Entity<C>)>,
+    /// The children of the `Entity`s in the `Scene` that have any
+    children: Vec<(Id, Vec<Id>)>,
+}
+
+impl<C> Default for Scene<C> {
+    fn default() -> Self {
+        Scene::new()
+    }
+}
+
+impl<C> Scene<C> {
+    /// Create an empty `Scene`
+    pub fn new() -> Scene<C> {
+        Scene {
+            entities: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+    /**
+    Create a `Scene` from copies of the `Entity`s with the given `Id`s
+
+    `Id`s that are not in the `World` or that are given more than once are skipped.
+    Parents and children are kept when both are in the `Scene`.
+    */
+    pub fn extract<I>(world: &World<C>, ids: I) -> Scene<C>
+    where
+        C: Clone,
+        I: IntoIterator<Item = Id>,
+    {
+        let mut scene = Scene::new();
+        let mut extracted = HashSet::new();
+        for id in ids {
+            if extracted.contains(&id) {
+                continue;
+            }
+            if let Some(entity) = world.clone_entity(id) {
+                extracted.insert(id);
+                scene.entities.push((id, entity));
+            }
+        }
+        for &(id, _) in &scene.entities {
+            let children: Vec<Id> = world
+                .children(id)
+                .iter()
+                .copied()
+                .filter(|child| extracted.contains(child))
+                .collect();
+            if !children.is_empty() {
+                scene.children.push((id, children));
+            }
+        }
+        scene
+    }
+    /// Get the number of `Entity`s in the `Scene`
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+    /// Check if the `Scene` has no `Entity`s
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+    /// Iterates through the `Entity`s in the `Scene` and the `Id`s they were extracted with
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &Entity<C>)> {
+        self.entities.iter().map(|(id, entity)| (*id, entity))
+    }
+    /// Get the `Id`s that components in the `Scene` refer to but that are not in the `Scene`
+    pub fn external_ids(&self) -> Vec<Id>
+    where
+        C: MapEntities,
+    {
+        let ids: HashSet<Id> = self.entities.iter().map(|&(id, _)| id).collect();
+        let mut seen = HashSet::new();
+        let mut external = Vec::new();
+        for (_, entity) in &self.entities {
+            entity.for_each_entity(&mut |id| {
+                if !ids.contains(&id) && seen.insert(id) {
+                    external.push(id);
+                }
+            });
+        }
+        external
+    }
+    /**
+    Add a copy of every `Entity` in the `Scene` to a `World`
+
+    Returns a map from the `Id`s the `Entity`s have in the `Scene`
+    to the `Id`s of their copies in the `World`. The commands recorded
+    by component hooks are applied once every `Entity` has been added.
+
+    Returns an error and adds nothing if components refer to `Entity`s
+    outside the `Scene`. Use `Scene::instantiate_with` to add it anyway.
+    */
+    pub fn instantiate(&self, world: &mut World<C>) -> Result<HashMap<Id, Id>, ExternalIds>
+    where
+        C: Clone + MapEntities,
+    {
+        let external = self.external_ids();
+        if !external.is_empty() {
+            return Err(ExternalIds(external));
+        }
+        Ok(self.instantiate_with(world, |id| id))
+    }
+    /**
+    Same as `Scene::instantiate`, but the `Id`s that refer to `Entity`s
+    outside the `Scene` are replaced by the result of `external`
+    */
+    pub fn instantiate_with<F>(&self, world: &mut World<C>, mut external: F) -> HashMap<Id, Id>
+    where
+        C: Clone + MapEntities,
+        F: FnMut(Id) -> Id,
+    {
+        let ids: HashMap<Id, Id> = self
+            .entities
+            .iter()
+            .map(|&(id, _)| (id, world.reserve()))
+            .collect();
+        let mut commands = Commands::new();
+        for (id, entity) in &self.entities {
+            let mut entity = entity.clone();
+            entity.map_entities(&mut |id| match ids.get(&id) {
+                Some(&id) => id,
+                None => external(id),
+            });
+            #[cfg(feature = "f_uuid")]
+            {
+                entity.uuid = Uuid::new_v4();
+            }
+            world.insert_at(ids[id], entity, &mut commands);
+        }
+        for (parent, children) in &self.children {
+            for child in children {
+                world.set_parent(ids[child], ids[parent]);
+            }
+        }
+        commands.apply(world);
+        ids
+    }
+}
+
+/// The error returned by `Scene::instantiate` when components refer to `Entity`s outside the `Scene`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalIds(pub Vec<Id>);
+
+impl fmt::Display for ExternalIds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The scene refers to entities outside of it:")?;
+        for id in &self.0 {
+            write!(f, " {}", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ExternalIds {}
+
+/**
+`Scene`s are serialized as a map from the `Id`s of their `Entity`s
+to the `Entity`s, and a map from parents to their children
+*/
+#[cfg(feature = "f_serde")]
+impl<C> Serialize for Scene<C>
+where
+    C: SerdeComponents,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Scene", 2)?;
+        state.serialize_field("entities", &SerMap(&self.entities))?;
+        state.serialize_field("children", &SerMap(&self.children))?;
+        state.end()
+    }
+}
+
+/// Serializes a list of pairs as a map
+#[cfg(feature = "f_serde")]
+struct SerMap<'a, K, V>(&'a [(K, V)]);
+
+#[cfg(feature = "f_serde")]
+impl<'a, K, V> Serialize for SerMap<'a, K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+    }
+}
+
+/// The serialized form of a `Scene`
+#[cfg(feature = "f_serde")]
+#[derive(Deserialize)]
+#[serde(bound = "C: SerdeComponents")]
+struct SceneData<C> {
+    entities: OrderedMap<Id, Entity<C>>,
+    #[serde(default)]
+    children: OrderedMap<Id, Vec<Id>>,
+}
+
+#[cfg(feature = "f_serde")]
+impl<'de, C> Deserialize<'de> for Scene<C>
+where
+    C: SerdeComponents,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = SceneData::<C>::deserialize(deserializer)?;
+        Scene::from_data(data).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "f_serde")]
+impl<C> Scene<C> {
+    /// Rebuild a `Scene` from its serialized form, checking that the `Id`s are consistent
+    fn from_data(data: SceneData<C>) -> Result<Scene<C>, String> {
+        let mut ids = HashSet::new();
+        for (id, _) in &data.entities.0 {
+            if !ids.insert(*id) {
+                return Err(format!("Entity id {} is used twice", id));
+            }
+        }
+        let mut hierarchy = Hierarchy::default();
+        for (parent, children) in &data.children.0 {
+            for &child in children {
+                if !ids.contains(parent) || !ids.contains(&child) {
+                    return Err(format!(
+                        "Entity {} or {} is not in the scene",
+                        child, parent
+                    ));
+                }
+                if hierarchy.parent(child).is_some() || hierarchy.is_ancestor(child, *parent) {
+                    return Err(format!("Entity {} cannot be a child of {}", child, parent));
+                }
+                hierarchy.set_parent(child, *parent);
+            }
+        }
+        Ok(Scene {
+            entities: data.entities.0,
+            children: data.children.0,
+        })
+    }
+}
@@ -0,0 +1,475 @@
+use std::{any::type_name, borrow::Cow, fmt};
+
+#[cfg(feature = "f_rayon")]
+use rayon::prelude::*;
+
+use crate::{Access, Commands, World, WorldView};
+
+/**
+Trait for logic that runs on a `World`
+
+This is implemented for every closure that takes a `&mut World`.
+A `System` has exclusive access to the `World`, so it never runs
+at the same time as any other system.
+*/
+pub trait System<C> {
+    /// Run the system on the `World`
+    fn run(&mut self, world: &mut World<C>);
+    /// Get the name of the system, which is used when reporting conflicts
+    fn name(&self) -> Cow<'static, str> {
+        type_name::<Self>().into()
+    }
+}
+
+impl<C, F> System<C> for F
+where
+    F: FnMut(&mut World<C>),
+{
+    fn run(&mut self, world: &mut World<C>) {
+        self(world)
+    }
+}
+
+/**
+Trait for logic that runs on a `WorldView` with declared `Access`
+
+Systems in the same stage of a `Schedule` whose `Access`es don't
+conflict run in parallel when the `f_rayon` feature is enabled.
+
+This is implemented for tuples of an `Access` and a closure that takes a `WorldView`.
+*/
+pub trait ParSystem<C>: Send {
+    /// Get the components the system reads and writes
+    ///
+    /// This is called once when the system is added to a `Schedule`.
+    fn access(&self) -> Access;
+    /// Run the system on a view of the `World`
+    fn run(&mut self, world: WorldView<'_, C>);
+    /// Get the name of the system, which is used when reporting conflicts
+    fn name(&self) -> Cow<'static, str> {
+        type_name::<Self>().into()
+    }
+}
+
+impl<C, F> ParSystem<C> for (Access, F)
+where
+    F: FnMut(WorldView<'_, C>) + Send,
+{
+    fn access(&self) -> Access {
+        self.0.clone()
+    }
+    fn run(&mut self, world: WorldView<'_, C>) {
+        (self.1)(world)
+    }
+    fn name(&self) -> Cow<'static, str> {
+        type_name::<F>().into()
+    }
+}
+
+/// Why two systems in the same stage cannot run at the same time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The name of the stage
+    pub stage: String,
+    /// The name of the system that runs first
+    pub first: String,
+    /// The name of the system that runs second
+    pub second: String,
+    /// The components that one system writes and the other accesses,
+    /// or `None` if one of them is a `System` with exclusive access to the `World`
+    pub components: Option<Vec<&'static str>>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} and {} in stage {:?} cannot run together because ",
+            self.first, self.second, self.stage
+        )?;
+        match &self.components {
+            Some(components) => write!(f, "they both access {}", components.join(", ")),
+            None => write!(f, "one of them has exclusive access to the world"),
+        }
+    }
+}
+
+/// A system in a `Stage`
+struct Node<C> {
+    kind: Kind<C>,
+    /// The tick the system last ran at
+    last_run: u64,
+}
+
+enum Kind<C> {
+    Exclusive(Box<dyn System<C>>),
+    Parallel {
+        system: Box<dyn ParSystem<C>>,
+        access: Access,
+        commands: Commands<C>,
+    },
+}
+
+impl<C> Node<C> {
+    fn new(kind: Kind<C>) -> Node<C> {
+        Node { kind, last_run: 0 }
+    }
+    fn name(&self) -> Cow<'static, str> {
+        match &self.kind {
+            Kind::Exclusive(system) => system.name(),
+            Kind::Parallel { system, .. } => system.name(),
+        }
+    }
+    /// Get the conflict between two systems, if there is one
+    fn conflict(&self, other: &Node<C>) -> Option<Option<Vec<&'static str>>> {
+        match (&self.kind, &other.kind) {
+            (Kind::Parallel { access: a, .. }, Kind::Parallel { access: b, .. }) => {
+                let components = a.conflicts(b);
+                if components.is_empty() {
+                    None
+                } else {
+                    Some(Some(components))
+                }
+            }
+            _ => Some(None),
+        }
+    }
+    fn run(&mut self, world: &mut World<C>) {
+        let tick = world.change_tick();
+        match &mut self.kind {
+            Kind::Exclusive(system) => {
+                let last_run = world.replace_last_run(self.last_run);
+                system.run(world);
+                world.replace_last_run(last_run);
+            }
+            Kind::Parallel {
+                system,
+                access,
+                commands,
+            } => system.run(WorldView::new(world, access, commands, self.last_run)),
+        }
+        self.last_run = tick;
+    }
+    /// Apply the commands the system recorded
+    fn apply(&mut self, world: &mut World<C>) {
+        if let Kind::Parallel { commands, .. } = &mut self.kind {
+            std::mem::take(commands).apply(world);
+        }
+    }
+}
+
+/// A named group of systems in a `Schedule`
+struct Stage<C> {
+    name: String,
+    systems: Vec<Node<C>>,
+    /// Groups of systems that don't conflict, in the order they run
+    batches: Vec<Vec<usize>>,
+}
+
+impl<C> Stage<C> {
+    fn push(&mut self, node: Node<C>) {
+        // The system has to run after the last batch
+        // that has a system it conflicts with
+        let batch = self
+            .batches
+            .iter()
+            .rposition(|batch| {
+                batch
+                    .iter()
+                    .any(|&i| self.systems[i].conflict(&node).is_some())
+            })
+            .map_or(0, |i| i + 1);
+        if batch == self.batches.len() {
+            self.batches.push(Vec::new());
+        }
+        self.batches[batch].push(self.systems.len());
+        self.systems.push(node);
+    }
+    fn conflicts(&self) -> impl Iterator<Item = Conflict> + '_ {
+        self.systems.iter().enumerate().flat_map(move |(i, first)| {
+            self.systems[i + 1..].iter().filter_map(move |second| {
+                Some(Conflict {
+                    stage: self.name.clone(),
+                    first: first.name().into_owned(),
+                    second: second.name().into_owned(),
+                    components: first.conflict(second)?,
+                })
+            })
+        })
+    }
+    fn run_sequential(&mut self, world: &mut World<C>) {
+        for batch in &self.batches {
+            world.increment_tick();
+            for &i in batch {
+                self.systems[i].run(world);
+            }
+        }
+        self.apply(world);
+    }
+    /// Apply the commands of every system in the order they were added
+    fn apply(&mut self, world: &mut World<C>) {
+        world.increment_tick();
+        for system in &mut self.systems {
+            system.apply(world);
+        }
+    }
+    #[cfg(feature = "f_rayon")]
+    fn run_parallel(&mut self, world: &mut World<C>)
+    where
+        C: Send + Sync,
+    {
+        for batch in &self.batches {
+            let tick = world.increment_tick();
+            if let [i] = batch[..] {
+                self.systems[i].run(world);
+                continue;
+            }
+            // Batches with more than one system only have `ParSystem`s
+            let world: &World<C> = world;
+            let mut batch = batch.iter().copied().peekable();
+            let systems: Vec<_> = self
+                .systems
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, _)| batch.next_if_eq(i).is_some())
+                .filter_map(|(_, node)| match &mut node.kind {
+                    Kind::Parallel {
+                        system,
+                        access,
+                        commands,
+                    } => Some((system, &*access, commands, &mut node.last_run)),
+                    Kind::Exclusive(_) => None,
+                })
+                .collect();
+            systems
+                .into_par_iter()
+                .for_each(|(system, access, commands, last_run)| {
+                    system.run(WorldView::new(world, access, commands, *last_run));
+                    *last_run = tick;
+                });
+        }
+        self.apply(world);
+    }
+}
+
+/**
+Runs systems in named stages
+
+Stages run in the order they were added. Within a stage, a system
+always runs after the systems added before it that it conflicts with.
+With the `f_rayon` feature, `ParSystem`s that don't conflict with
+each other run in parallel. Use `Schedule::conflicts` to find out
+which systems could not run together and why.
+
+`Added` and `Changed` filters in a system only match components
+that were added or changed since that system last ran, and
+`World::removed` only returns the removals since then.
+
+# Example
+```
+use eks::*;
+
+component! { Position: isize, Speed: isize }
+
+let mut schedule = Schedule::new()
+    .with_stage("update")
+    .with_stage_before("update", "input")
+    .with_system("update", |world: &mut World<Comp>| {
+        for (position, speed) in map_mut!(Position, Speed in world) {
+            *position += *speed;
+        }
+    })
+    .with_system("input", |world: &mut World<Comp>| {
+        for speed in map_mut!(Speed in world) {
+            *speed *= 2;
+        }
+    });
+
+let mut world = World::new();
+let id = world.insert(entity! { Position: 0, Speed: 1 });
+schedule.run(&mut world);
+assert_eq!(2, world[id][Position]);
+```
+*/
+pub struct Schedule<C> {
+    stages: Vec<Stage<C>>,
+}
+
+impl<C> Default for Schedule<C> {
+    fn default() -> Self {
+        Schedule::new()
+    }
+}
+
+impl<C> Schedule<C> {
+    /// Create a new `Schedule` with no stages
+    pub fn new() -> Schedule<C> {
+        Schedule { stages: Vec::new() }
+    }
+    /**
+    Add a stage after all the other stages
+
+    # Panics
+
+    Panics if a stage with the same name already exists
+    */
+    pub fn add_stage(&mut self, name: &str) -> &mut Self {
+        let index = self.stages.len();
+        self.insert_stage(index, name)
+    }
+    /**
+    Add a stage right before another one
+
+    # Panics
+
+    Panics if `target` does not exist or if `name` already exists
+    */
+    pub fn add_stage_before(&mut self, target: &str, name: &str) -> &mut Self {
+        let index = self.stage_index(target);
+        self.insert_stage(index, name)
+    }
+    /**
+    Add a stage right after another one
+
+    # Panics
+
+    Panics if `target` does not exist or if `name` already exists
+    */
+    pub fn add_stage_after(&mut self, target: &str, name: &str) -> &mut Self {
+        let index = self.stage_index(target) + 1;
+        self.insert_stage(index, name)
+    }
+    /**
+    Add a system to the end of a stage
+
+    # Panics
+
+    Panics if the stage does not exist
+    */
+    pub fn add_system<S>(&mut self, stage: &str, system: S) -> &mut Self
+    where
+        S: System<C> + 'static,
+    {
+        let index = self.stage_index(stage);
+        self.stages[index].push(Node::new(Kind::Exclusive(Box::new(system))));
+        self
+    }
+    /**
+    Add a system with declared `Access` to the end of a stage
+
+    # Panics
+
+    Panics if the stage does not exist
+    */
+    pub fn add_par_system<S>(&mut self, stage: &str, system: S) -> &mut Self
+    where
+        S: ParSystem<C> + 'static,
+    {
+        let index = self.stage_index(stage);
+        let access = system.access();
+        self.stages[index].push(Node::new(Kind::Parallel {
+            system: Box::new(system),
+            access,
+            commands: Commands::new(),
+        }));
+        self
+    }
+    /// Add a stage after all the other stages
+    pub fn with_stage(mut self, name: &str) -> Self {
+        self.add_stage(name);
+        self
+    }
+    /// Add a stage right before another one
+    pub fn with_stage_before(mut self, target: &str, name: &str) -> Self {
+        self.add_stage_before(target, name);
+        self
+    }
+    /// Add a stage right after another one
+    pub fn with_stage_after(mut self, target: &str, name: &str) -> Self {
+        self.add_stage_after(target, name);
+        self
+    }
+    /// Add a system to the end of a stage
+    pub fn with_system<S>(mut self, stage: &str, system: S) -> Self
+    where
+        S: System<C> + 'static,
+    {
+        self.add_system(stage, system);
+        self
+    }
+    /// Add a system with declared `Access` to the end of a stage
+    pub fn with_par_system<S>(mut self, stage: &str, system: S) -> Self
+    where
+        S: ParSystem<C> + 'static,
+    {
+        self.add_par_system(stage, system);
+        self
+    }
+    /// Iterates through the names of the stages in the order they run
+    pub fn stages(&self) -> impl Iterator<Item = &str> {
+        self.stages.iter().map(|stage| stage.name.as_str())
+    }
+    /// Get every pair of systems in the same stage that cannot run at the same time
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        self.stages.iter().flat_map(Stage::conflicts).collect()
+    }
+    /// Run every system in every stage once
+    #[cfg(not(feature = "f_rayon"))]
+    pub fn run(&mut self, world: &mut World<C>) {
+        for stage in &mut self.stages {
+            stage.run_sequential(world);
+        }
+        self.finish_run(world);
+    }
+    /// Run every system in every stage once
+    #[cfg(feature = "f_rayon")]
+    pub fn run(&mut self, world: &mut World<C>)
+    where
+        C: Send + Sync,
+    {
+        for stage in &mut self.stages {
+            stage.run_parallel(world);
+        }
+        self.finish_run(world);
+    }
+    /// Run every system in every stage once without running any systems in parallel
+    pub fn run_sequential(&mut self, world: &mut World<C>) {
+        for stage in &mut self.stages {
+            stage.run_sequential(world);
+        }
+        self.finish_run(world);
+    }
+    /// Forget the removals that every system has seen and update the events
+    fn finish_run(&self, world: &mut World<C>) {
+        world.update_events();
+        let seen = self
+            .stages
+            .iter()
+            .flat_map(|stage| &stage.systems)
+            .map(|system| system.last_run)
+            .min();
+        if let Some(seen) = seen {
+            world.forget_removed(seen);
+        }
+    }
+    fn stage_index(&self, name: &str) -> usize {
+        self.stages
+            .iter()
+            .position(|stage| stage.name == name)
+            .unwrap_or_else(|| panic!("Unable to find stage {:?}", name))
+    }
+    fn insert_stage(&mut self, index: usize, name: &str) -> &mut Self {
+        if self.stages.iter().any(|stage| stage.name == name) {
+            panic!("Stage {:?} already exists", name);
+        }
+        self.stages.insert(
+            index,
+            Stage {
+                name: name.into(),
+                systems: Vec::new(),
+                batches: Vec::new(),
+            },
+        );
+        self
+    }
+}
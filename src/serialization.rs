@@ -0,0 +1,328 @@
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+#[cfg(feature = "f_uuid")]
+use uuid::Uuid;
+
+use crate::{AsRow, Entity, EntityRef, Id, Row};
+
+/**
+Trait for component enums whose values can be serialized
+
+You do not need to implement this trait manually. With the `f_serde` feature,
+the `component!` macro implements it for component enums marked with `#serde`,
+which requires the type of each of their components to implement
+`Serialize` and `DeserializeOwned`.
+*/
+pub trait SerdeComponents: Sized {
+    /// The names of all the components in the enum
+    const NAMES: &'static [&'static str];
+    /// The versions of the components, in the same order as `NAMES`
+    const VERSIONS: &'static [u32];
+    /// Serialize the value of the component with the given name in a `Row`, without its name
+    fn serialize_component<S>(
+        row: Row<'_, Self>,
+        name: &str,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer;
+    /// Deserialize the value of the component with the given name and add it to an `Entity`
+    fn deserialize_into<'de, D>(
+        name: &str,
+        deserializer: D,
+        entity: &mut Entity<Self>,
+    ) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>;
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! serde_components {
+    ($name:ident { $($id:ident),* }) => {
+        impl eks::SerdeComponents for $name {
+            const NAMES: &'static [&'static str] = &[$(stringify!($id)),*];
+            const VERSIONS: &'static [u32] = &[$(<$id as eks::Component>::VERSION),*];
+            fn serialize_component<S>(
+                row: eks::Row<'_, Self>,
+                name: &str,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error>
+            where
+                S: eks::serde::Serializer,
+            {
+                let value = match name {
+                    $(stringify!($id) => row.get::<$id>().map(|val| eks::serde::Serialize::serialize(val, serializer)),)*
+                    _ => None,
+                };
+                value.unwrap_or_else(|| {
+                    Err(<S::Error as eks::serde::ser::Error>::custom(format!(
+                        "The entity has no component {}",
+                        name
+                    )))
+                })
+            }
+            fn deserialize_into<'de, D>(
+                name: &str,
+                deserializer: D,
+                entity: &mut eks::Entity<Self>,
+            ) -> Result<(), D::Error>
+            where
+                D: eks::serde::Deserializer<'de>,
+            {
+                match name {
+                    $(stringify!($id) => {
+                        entity.add::<$id>(eks::serde::Deserialize::deserialize(deserializer)?);
+                        Ok(())
+                    })*
+                    _ => Err(<D::Error as eks::serde::de::Error>::unknown_field(name, Self::NAMES)),
+                }
+            }
+        }
+    };
+}
+
+/**
+`Id`s are serialized as `"{index}v{generation}"` strings in human-readable
+formats, the same as their `Display` output, and as `(index, generation)` otherwise
+*/
+impl Serialize for Id {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            (self.index, self.generation).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            s.split_once('v')
+                .and_then(|(index, generation)| {
+                    Some(Id {
+                        index: index.parse().ok()?,
+                        generation: generation.parse().ok()?,
+                    })
+                })
+                .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(&s), &"an id like 3v1"))
+        } else {
+            let (index, generation) = Deserialize::deserialize(deserializer)?;
+            Ok(Id { index, generation })
+        }
+    }
+}
+
+/// Serializes the value of the component with the given name in a `Row`, without its name
+pub(crate) struct Value<'a, C>(pub(crate) Row<'a, C>, pub(crate) &'static str);
+
+impl<'a, C> Serialize for Value<'a, C>
+where
+    C: SerdeComponents,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        C::serialize_component(self.0, self.1, serializer)
+    }
+}
+
+/**
+`Entity`s are serialized as maps from their components' names to their values
+
+With the `f_uuid` feature, the uuid is stored under the `"uuid"` key, so the `Entity`s
+of a component enum with a component named `uuid` fail to serialize and deserialize.
+The `Id` is not part of the `Entity`'s data, so a deserialized `Entity`
+is not in any `World`.
+*/
+impl<C> Serialize for Entity<C>
+where
+    C: SerdeComponents,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[cfg(feature = "f_uuid")]
+        check_uuid_key::<C>().map_err(serde::ser::Error::custom)?;
+        let mut map = serializer.serialize_map(None)?;
+        #[cfg(feature = "f_uuid")]
+        map.serialize_entry(UUID_KEY, &self.uuid.to_string())?;
+        serialize_components(&mut map, self.row())?;
+        map.end()
+    }
+}
+
+/// `EntityRef`s are serialized the same way as `Entity`s
+impl<C> Serialize for EntityRef<'_, C>
+where
+    C: SerdeComponents,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[cfg(feature = "f_uuid")]
+        check_uuid_key::<C>().map_err(serde::ser::Error::custom)?;
+        let mut map = serializer.serialize_map(None)?;
+        #[cfg(feature = "f_uuid")]
+        map.serialize_entry(UUID_KEY, &self.uuid().to_string())?;
+        serialize_components(&mut map, self.row())?;
+        map.end()
+    }
+}
+
+/// The key that the uuid of an `Entity` is stored under
+#[cfg(feature = "f_uuid")]
+const UUID_KEY: &str = "uuid";
+
+/// Fail if a component has the same name as the uuid key, which it could not be told apart from
+#[cfg(feature = "f_uuid")]
+fn check_uuid_key<C>() -> Result<(), String>
+where
+    C: SerdeComponents,
+{
+    if C::NAMES.contains(&UUID_KEY) {
+        Err(format!(
+            "The component {} has the same name as the key of the uuid",
+            UUID_KEY
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Add the components of a `Row` to a map from their names to their values
+fn serialize_components<C, M>(map: &mut M, row: Row<'_, C>) -> Result<(), M::Error>
+where
+    C: SerdeComponents,
+    M: SerializeMap,
+{
+    for name in row.names() {
+        map.serialize_entry(name, &Value(row, name))?;
+    }
+    Ok(())
+}
+
+impl<'de, C> Deserialize<'de> for Entity<C>
+where
+    C: SerdeComponents,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(EntityVisitor(PhantomData))
+    }
+}
+
+struct EntityVisitor<C>(PhantomData<C>);
+
+impl<'de, C> Visitor<'de> for EntityVisitor<C>
+where
+    C: SerdeComponents,
+{
+    type Value = Entity<C>;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of component names to values")
+    }
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        #[cfg(feature = "f_uuid")]
+        check_uuid_key::<C>().map_err(de::Error::custom)?;
+        let mut entity = Entity::new();
+        while let Some(name) = map.next_key::<String>()? {
+            #[cfg(feature = "f_uuid")]
+            {
+                if name == UUID_KEY {
+                    let uuid: String = map.next_value()?;
+                    entity.uuid = Uuid::parse_str(&uuid).map_err(de::Error::custom)?;
+                    continue;
+                }
+            }
+            map.next_value_seed(ComponentSeed {
+                name: &name,
+                entity: &mut entity,
+            })?;
+        }
+        Ok(entity)
+    }
+}
+
+/// Deserializes the value of a component and adds it to an `Entity`
+struct ComponentSeed<'a, C> {
+    name: &'a str,
+    entity: &'a mut Entity<C>,
+}
+
+impl<'a, 'de, C> DeserializeSeed<'de> for ComponentSeed<'a, C>
+where
+    C: SerdeComponents,
+{
+    type Value = ();
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        C::deserialize_into(self.name, deserializer, self.entity)
+    }
+}
+
+/// A map that keeps the order of its entries when it is deserialized
+pub(crate) struct OrderedMap<K, V>(pub(crate) Vec<(K, V)>);
+
+impl<K, V> Default for OrderedMap<K, V> {
+    fn default() -> Self {
+        OrderedMap(Vec::new())
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for OrderedMap<K, V>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct OrderedMapVisitor<K, V>(PhantomData<(K, V)>);
+        impl<'de, K, V> Visitor<'de> for OrderedMapVisitor<K, V>
+        where
+            K: Deserialize<'de>,
+            V: Deserialize<'de>,
+        {
+            type Value = OrderedMap<K, V>;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map")
+            }
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(OrderedMap(entries))
+            }
+        }
+        deserializer.deserialize_map(OrderedMapVisitor(PhantomData))
+    }
+}
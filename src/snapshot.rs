@@ -0,0 +1,526 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "f_uuid")]
+use uuid::Uuid;
+
+use crate::{
+    serialization::{OrderedMap, Value},
+    world::WorldData,
+    AsRow, Entity, Id, SerdeComponents, World,
+};
+
+/// The bytes every snapshot starts with
+const MAGIC: [u8; 4] = *b"EKSS";
+
+/// The version of the snapshot format written and read by `World::to_snapshot`
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The options used to encode every part of a snapshot
+fn options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+/// An error that occurs when writing, migrating or reading a snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The data does not start like a snapshot
+    NotASnapshot,
+    /// The snapshot was written in a version of the format that is not supported
+    UnsupportedVersion(u32),
+    /// The snapshot's schema version is newer than the `Schema`'s
+    UnsupportedSchema(u32),
+    /// The `Schema` has no migration from the given schema version
+    MissingMigration(u32),
+    /// The snapshot has components that are not in the component enum
+    UnknownComponents(Vec<String>),
+    /// A component in the snapshot has a different version than in the component enum
+    VersionMismatch {
+        /// The name of the component
+        component: String,
+        /// The version of the component in the component enum
+        expected: u32,
+        /// The version of the component in the snapshot
+        found: u32,
+    },
+    /// The snapshot is malformed, or a component could not be encoded or decoded
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::NotASnapshot => write!(f, "The data is not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "Snapshot format version {} is not supported", version)
+            }
+            SnapshotError::UnsupportedSchema(version) => {
+                write!(
+                    f,
+                    "Schema version {} is newer than the current schema",
+                    version
+                )
+            }
+            SnapshotError::MissingMigration(version) => {
+                write!(f, "There is no migration from schema version {}", version)
+            }
+            SnapshotError::UnknownComponents(unknown) => write!(
+                f,
+                "The snapshot has components that are not in the component enum: {}",
+                unknown.join(", ")
+            ),
+            SnapshotError::VersionMismatch {
+                component,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Component {} has version {} in the snapshot, but {} in the component enum",
+                component, found, expected
+            ),
+            SnapshotError::Invalid(message) => write!(f, "Invalid snapshot: {}", message),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(error: bincode::Error) -> Self {
+        SnapshotError::Invalid(error.to_string())
+    }
+}
+
+/// The start of a snapshot after the format version
+#[derive(Serialize, Deserialize)]
+struct Header {
+    schema: u32,
+    /// The names and versions of the components, which the entities refer to by position
+    components: Vec<(String, u32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Body {
+    generations: Vec<u32>,
+    free: Vec<u32>,
+    entities: Vec<EntityRecord>,
+    children: Vec<(Id, Vec<Id>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EntityRecord {
+    id: Id,
+    /// Only written with the `f_uuid` feature
+    uuid: Option<[u8; 16]>,
+    /// The position of each component in the header and its encoded value
+    components: Vec<(u32, Vec<u8>)>,
+}
+
+/// A component in the header of a `RawSnapshot`
+struct ComponentInfo {
+    name: String,
+    version: u32,
+}
+
+/// A migration from one schema version to the next
+type Migration = Box<dyn Fn(&mut RawSnapshot) -> Result<(), SnapshotError>>;
+
+/**
+The version of a component enum and the migrations from its older versions
+
+Every snapshot records the schema version it was written with.
+When a snapshot with an older schema version is read, the migrations
+from its version up to the current one are run on it in order,
+before any of its components are decoded.
+
+# Example
+```
+use eks::*;
+
+// An old version of the game
+mod old {
+    use eks::*;
+    component! { #serde Position: i32, Speed: i32, Debug: () }
+
+    pub fn save() -> Vec<u8> {
+        let mut world = World::new();
+        world.insert(entity! { Position: 1, Speed: 2, Debug: () });
+        world.to_snapshot().unwrap()
+    }
+}
+let snapshot = old::save();
+
+// `Speed` became a float, `Position` was renamed to `X` and `Debug` was removed
+component! { #serde X: i32, #version(1) Speed: f32 }
+let schema = Schema::new(1).with_migration(0, |snapshot| {
+    snapshot.rename("Position", "X");
+    snapshot.transform("Speed", |speed: i32| speed as f32)?;
+    snapshot.set_version("Speed", 1);
+    snapshot.remove("Debug");
+    Ok(())
+});
+
+let world = World::<Comp>::from_snapshot_with(&snapshot, &schema).unwrap();
+let entity = world.iter().next().unwrap();
+assert_eq!(1, entity[X]);
+assert_eq!(2.0, entity[Speed]);
+```
+*/
+pub struct Schema {
+    version: u32,
+    migrations: HashMap<u32, Migration>,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema::new(0)
+    }
+}
+
+impl Schema {
+    /// Create a `Schema` with the given version and no migrations
+    pub fn new(version: u32) -> Schema {
+        Schema {
+            version,
+            migrations: HashMap::new(),
+        }
+    }
+    /// Get the `Schema`'s version
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+    /// Add the migration from schema version `from` to `from + 1`
+    ///
+    /// A previous migration from the same version is replaced.
+    pub fn add_migration<F>(&mut self, from: u32, migration: F) -> &mut Self
+    where
+        F: Fn(&mut RawSnapshot) -> Result<(), SnapshotError> + 'static,
+    {
+        self.migrations.insert(from, Box::new(migration));
+        self
+    }
+    /// Builder version of `Schema::add_migration`
+    pub fn with_migration<F>(mut self, from: u32, migration: F) -> Self
+    where
+        F: Fn(&mut RawSnapshot) -> Result<(), SnapshotError> + 'static,
+    {
+        self.add_migration(from, migration);
+        self
+    }
+}
+
+/**
+A snapshot whose components have not been decoded yet
+
+Migrations registered with `Schema::add_migration` change its
+components before they are decoded into the current component enum.
+*/
+pub struct RawSnapshot {
+    schema: u32,
+    components: Vec<ComponentInfo>,
+    body: Body,
+}
+
+impl RawSnapshot {
+    fn read(bytes: &[u8]) -> Result<RawSnapshot, SnapshotError> {
+        let mut reader = match bytes.strip_prefix(&MAGIC[..]) {
+            Some(rest) => rest,
+            None => return Err(SnapshotError::NotASnapshot),
+        };
+        let version: u32 = options().deserialize_from(&mut reader)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let header: Header = options().deserialize_from(&mut reader)?;
+        let body: Body = options().deserialize_from(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(SnapshotError::Invalid("Trailing bytes".into()));
+        }
+        let components = header
+            .components
+            .into_iter()
+            .map(|(name, version)| ComponentInfo { name, version })
+            .collect();
+        Ok(RawSnapshot {
+            schema: header.schema,
+            components,
+            body,
+        })
+    }
+    /// Get the schema version of the snapshot
+    ///
+    /// While a migration runs, this is the version it migrates from.
+    pub fn schema(&self) -> u32 {
+        self.schema
+    }
+    /// Iterates through the names of the components in the snapshot
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.components.iter().map(|info| info.name.as_str())
+    }
+    /// Get the version of a component, or `None` if it is not in the snapshot
+    ///
+    /// Components without a `#version` in the `component!` macro have version 0.
+    pub fn version(&self, name: &str) -> Option<u32> {
+        self.position(name).map(|i| self.components[i].version)
+    }
+    /// Set the version of a component, usually after it was transformed
+    pub fn set_version(&mut self, name: &str, version: u32) {
+        if let Some(i) = self.position(name) {
+            self.components[i].version = version;
+        }
+    }
+    fn position(&self, name: &str) -> Option<usize> {
+        self.components.iter().position(|info| info.name == name)
+    }
+    /// Rename a component
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Some(i) = self.position(from) {
+            self.components[i].name = to.into();
+        }
+    }
+    /// Remove a component from every `Entity`
+    pub fn remove(&mut self, name: &str) {
+        let removed = match self.position(name) {
+            Some(i) => i as u32,
+            None => return,
+        };
+        self.components.remove(removed as usize);
+        for record in &mut self.body.entities {
+            record.components.retain(|(index, _)| *index != removed);
+            for (index, _) in &mut record.components {
+                if *index > removed {
+                    *index -= 1;
+                }
+            }
+        }
+    }
+    /**
+    Decode every value of a component as a `T`, and replace it with the result of `f`
+
+    The type of the component becomes `U`, so its version usually has to be
+    updated with `RawSnapshot::set_version`. Fails if a value is not a `T`, including
+    when decoding it as a `T` leaves some of its bytes unread.
+    */
+    pub fn transform<T, U, F>(&mut self, name: &str, mut f: F) -> Result<(), SnapshotError>
+    where
+        T: DeserializeOwned,
+        U: Serialize,
+        F: FnMut(T) -> U,
+    {
+        let transformed = match self.position(name) {
+            Some(i) => i,
+            None => return Ok(()),
+        };
+        for record in &mut self.body.entities {
+            for (index, value) in &mut record.components {
+                if *index as usize == transformed {
+                    let mut reader = &value[..];
+                    let old: T = options().deserialize_from(&mut reader)?;
+                    if !reader.is_empty() {
+                        return Err(SnapshotError::Invalid(format!(
+                            "Trailing bytes after a value of {}",
+                            name
+                        )));
+                    }
+                    *value = options().serialize(&f(old))?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Decode the components and build a `World`
+    fn into_world<C>(self) -> Result<World<C>, SnapshotError>
+    where
+        C: SerdeComponents,
+    {
+        for (i, info) in self.components.iter().enumerate() {
+            if self.components[..i]
+                .iter()
+                .any(|other| other.name == info.name)
+            {
+                return Err(SnapshotError::Invalid(format!(
+                    "Component {} appears twice",
+                    info.name
+                )));
+            }
+        }
+        let unknown: Vec<String> = self
+            .names()
+            .filter(|name| !C::NAMES.contains(name))
+            .map(Into::into)
+            .collect();
+        // Components of the enum that are not in the snapshot were added since
+        // it was written, so no `Entity` has them yet
+        if !unknown.is_empty() {
+            return Err(SnapshotError::UnknownComponents(unknown));
+        }
+        for (&name, &expected) in C::NAMES.iter().zip(C::VERSIONS) {
+            match self.version(name) {
+                Some(found) if found != expected => {
+                    return Err(SnapshotError::VersionMismatch {
+                        component: name.into(),
+                        expected,
+                        found,
+                    })
+                }
+                _ => {}
+            }
+        }
+        let mut entities = Vec::with_capacity(self.body.entities.len());
+        for record in self.body.entities {
+            let mut entity = Entity::new();
+            #[cfg(feature = "f_uuid")]
+            {
+                if let Some(uuid) = record.uuid {
+                    entity.uuid = Uuid::from_bytes(uuid);
+                }
+            }
+            for (index, value) in record.components {
+                let info = self.components.get(index as usize).ok_or_else(|| {
+                    SnapshotError::Invalid(format!("Component index {} is out of range", index))
+                })?;
+                let mut reader = &value[..];
+                let mut deserializer = bincode::Deserializer::with_reader(&mut reader, options());
+                C::deserialize_into(&info.name, &mut deserializer, &mut entity)?;
+                if !reader.is_empty() {
+                    return Err(SnapshotError::Invalid(format!(
+                        "Trailing bytes after a value of {}",
+                        info.name
+                    )));
+                }
+            }
+            entities.push((record.id, entity));
+        }
+        World::from_data(WorldData {
+            generations: self.body.generations,
+            free: self.body.free,
+            entities: OrderedMap(entities),
+            children: OrderedMap(self.body.children),
+        })
+        .map_err(SnapshotError::Invalid)
+    }
+}
+
+impl<C> World<C>
+where
+    C: SerdeComponents,
+{
+    /**
+    Write the `World` to a compact binary snapshot with schema version 0
+
+    Snapshots start with a header that lists the names and versions of
+    the components, followed by the `Entity`s with their `Id`s, and
+    the parents and children. They contain the same data as a serialized
+    `World`, so resources, relations, change ticks and removed components
+    are not included.
+
+    Fails if the value of a component cannot be encoded.
+
+    # Example
+    ```
+    use eks::*;
+
+    component! { #serde Position: i32, Target: Id }
+
+    let mut world = World::new();
+    let a = world.insert(entity! { Position: 1 });
+    let b = world.insert(entity! { Position: 2, Target: a });
+
+    let snapshot = world.to_snapshot().unwrap();
+    let loaded = World::<Comp>::from_snapshot(&snapshot).unwrap();
+    assert_eq!(a, loaded[b][Target]);
+
+    // Loading with a component enum that has no `Target` fails
+    mod current {
+        use eks::*;
+        component! { #serde Position: i32 }
+    }
+    assert_eq!(
+        Err(SnapshotError::UnknownComponents(vec!["Target".into()])),
+        World::<current::Comp>::from_snapshot(&snapshot).map(drop)
+    );
+    ```
+    */
+    pub fn to_snapshot(&self) -> Result<Vec<u8>, SnapshotError> {
+        self.to_snapshot_with(&Schema::default())
+    }
+    /// Write the `World` to a compact binary snapshot with the `Schema`'s version
+    pub fn to_snapshot_with(&self, schema: &Schema) -> Result<Vec<u8>, SnapshotError> {
+        let header = Header {
+            schema: schema.version,
+            components: C::NAMES
+                .iter()
+                .zip(C::VERSIONS)
+                .map(|(&name, &version)| (name.into(), version))
+                .collect(),
+        };
+        let mut entities = Vec::with_capacity(self.len());
+        for entity in self.iter() {
+            let mut components = Vec::new();
+            let row = entity.row();
+            for name in row.names() {
+                let index = C::NAMES
+                    .iter()
+                    .position(|&other| other == name)
+                    .expect("Component is not in the component enum");
+                components.push((index as u32, options().serialize(&Value(row, name))?));
+            }
+            entities.push(EntityRecord {
+                id: entity.id(),
+                #[cfg(feature = "f_uuid")]
+                uuid: Some(*entity.uuid().as_bytes()),
+                #[cfg(not(feature = "f_uuid"))]
+                uuid: None,
+                components,
+            });
+        }
+        let body = Body {
+            generations: self.generations(),
+            free: self.free(),
+            entities,
+            children: self
+                .iter()
+                .map(|entity| entity.id())
+                .map(|id| (id, self.children(id).to_vec()))
+                .filter(|(_, children)| !children.is_empty())
+                .collect(),
+        };
+        let mut bytes = MAGIC.to_vec();
+        options().serialize_into(&mut bytes, &SNAPSHOT_VERSION)?;
+        options().serialize_into(&mut bytes, &header)?;
+        options().serialize_into(&mut bytes, &body)?;
+        Ok(bytes)
+    }
+    /**
+    Read a `World` from a snapshot with schema version 0
+
+    Fails if the snapshot has components that are not in the component enum,
+    if their versions differ, or if the snapshot is malformed. Components of the enum
+    that are not in the snapshot are fine: no `Entity` has them.
+    */
+    pub fn from_snapshot(bytes: &[u8]) -> Result<World<C>, SnapshotError> {
+        World::from_snapshot_with(bytes, &Schema::default())
+    }
+    /**
+    Read a `World` from a snapshot, migrating it to the `Schema`'s version
+
+    Fails if the snapshot's schema version is newer than the `Schema`'s,
+    if a migration is missing or fails, or in the same cases as `World::from_snapshot`.
+    */
+    pub fn from_snapshot_with(bytes: &[u8], schema: &Schema) -> Result<World<C>, SnapshotError> {
+        let mut snapshot = RawSnapshot::read(bytes)?;
+        if snapshot.schema > schema.version {
+            return Err(SnapshotError::UnsupportedSchema(snapshot.schema));
+        }
+        while snapshot.schema < schema.version {
+            let migration = schema
+                .migrations
+                .get(&snapshot.schema)
+                .ok_or(SnapshotError::MissingMigration(snapshot.schema))?;
+            migration(&mut snapshot)?;
+            snapshot.schema += 1;
+        }
+        snapshot.into_world()
+    }
+}
@@ -0,0 +1,777 @@
+use std::{any::Any, cell::UnsafeCell, collections::BTreeMap};
+
+#[cfg(feature = "f_uuid")]
+use uuid::Uuid;
+
+use crate::{
+    query::Either,
+    relation::{RelationTable, Relations},
+    Component, ComponentSet, Entity, Id, Relation, Slot, Storage,
+};
+
+/**
+The components of a single `Entity`, which a `ComponentSet` or a `Filter` borrows from
+
+This is either an `Entity` that is not in a `World`,
+or a row of one of the archetypes of a `World`.
+*/
+pub struct Row<'a, C> {
+    kind: RowKind<'a, C>,
+}
+
+enum RowKind<'a, C> {
+    Entity(&'a Entity<C>),
+    Table {
+        archetype: &'a Archetype<C>,
+        row: usize,
+        sparse: &'a SparseSets<C>,
+        relations: &'a Relations,
+    },
+}
+
+impl<'a, C> Clone for Row<'a, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, C> Copy for Row<'a, C> {}
+
+impl<'a, C> Clone for RowKind<'a, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, C> Copy for RowKind<'a, C> {}
+
+impl<'a, C> Row<'a, C> {
+    /// Get the `Row` of an `Entity` that is not in a `World`
+    pub(crate) fn entity(entity: &'a Entity<C>) -> Row<'a, C> {
+        Row {
+            kind: RowKind::Entity(entity),
+        }
+    }
+    /// Get the `Id` of the `Entity`, if it is in a `World`
+    pub(crate) fn id(self) -> Option<Id> {
+        match self.kind {
+            RowKind::Entity(_) => None,
+            RowKind::Table { archetype, row, .. } => Some(archetype.ids[row]),
+        }
+    }
+    /// Get the `Table` that the `Entity` is in
+    pub(crate) fn table(self) -> Table<'a, C> {
+        match self.kind {
+            RowKind::Entity(entity) => Table {
+                kind: TableKind::Entity(entity),
+            },
+            RowKind::Table {
+                archetype,
+                sparse,
+                relations,
+                ..
+            } => archetype.table(sparse, relations),
+        }
+    }
+    /// Get the position of the `Entity` in its `Table`
+    ///
+    /// An `Entity` that is not in a `World` is the only one in its `Table`.
+    fn position(self) -> usize {
+        match self.kind {
+            RowKind::Entity(_) => 0,
+            RowKind::Table { row, .. } => row,
+        }
+    }
+    /// Get the position of the `Entity`'s value in a sparse set of its `World`
+    fn sparse_position(self, set: &SparseSet<C>) -> Option<usize> {
+        match self.kind {
+            RowKind::Entity(_) => None,
+            RowKind::Table { archetype, row, .. } => set.position(archetype.ids[row].index),
+        }
+    }
+    /// Get a reference to a component's value
+    pub fn get<T>(self) -> Option<&'a T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        self.fetch::<T>()
+    }
+    /// Check if the `Entity` has a component, without borrowing its value
+    pub(crate) fn has<T>(self) -> bool
+    where
+        T: Component<Enum = C>,
+    {
+        self.table()
+            .column::<T>()
+            .is_some_and(|column| column.contains(self))
+    }
+    /// Same as `AsRow::fetch`
+    pub(crate) fn fetch<T>(self) -> Option<T::Ref>
+    where
+        T: ComponentSet<'a, C>,
+    {
+        T::fetch(self, T::columns(self.table())?)
+    }
+    /// Iterates through the names of the table components, then of the sparse ones
+    pub(crate) fn names(self) -> impl Iterator<Item = &'static str> + 'a {
+        match self.kind {
+            RowKind::Entity(entity) => Either::Left(entity.names().chain(entity.sparse_names())),
+            RowKind::Table {
+                archetype,
+                row,
+                sparse,
+                ..
+            } => {
+                let index = archetype.ids[row].index;
+                Either::Right(
+                    archetype.components.iter().copied().chain(
+                        sparse
+                            .iter()
+                            .filter(move |(_, set)| set.contains(index))
+                            .map(|(name, _)| *name),
+                    ),
+                )
+            }
+        }
+    }
+}
+
+/**
+A group of `Entity`s that have the same table components
+
+This is either an `Entity` that is not in a `World`, or one of the
+archetypes of a `World` together with the `World`'s sparse sets and relations.
+A `ComponentSet` or a `Filter` finds its components in a `Table` once,
+and then reads them from each of its `Row`s.
+*/
+pub struct Table<'a, C> {
+    kind: TableKind<'a, C>,
+}
+
+enum TableKind<'a, C> {
+    Entity(&'a Entity<C>),
+    Archetype(&'a Archetype<C>, &'a SparseSets<C>, &'a Relations),
+}
+
+impl<'a, C> Clone for Table<'a, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, C> Copy for Table<'a, C> {}
+
+impl<'a, C> Clone for TableKind<'a, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, C> Copy for TableKind<'a, C> {}
+
+impl<'a, C> Table<'a, C> {
+    /**
+    Find where the values of a component are
+
+    Returns `None` if no `Entity` in the `Table` has the component.
+    Sparse components are not part of an archetype, so their sparse set is found instead.
+    */
+    pub(crate) fn column<T>(self) -> Option<ColumnRef<'a, T>>
+    where
+        T: Component<Enum = C>,
+    {
+        let kind = match self.kind {
+            TableKind::Entity(entity) => ColumnKind::Slot(entity.slot(T::AS_STR, T::STORAGE)?),
+            TableKind::Archetype(archetype, sparse, _) => match T::STORAGE {
+                Storage::Table => ColumnKind::Column(archetype.column::<T>()?),
+                Storage::Sparse => match sparse.get(T::AS_STR) {
+                    Some(set) => ColumnKind::Sparse(set, set.column::<T>()?),
+                    None => ColumnKind::Empty,
+                },
+            },
+        };
+        Some(ColumnRef { kind })
+    }
+    /// Find the pairs of a `Relation`, which an `Entity` outside of a `World` has none of
+    pub(crate) fn relation<R>(self) -> Option<&'a RelationTable>
+    where
+        R: Relation,
+    {
+        match self.kind {
+            TableKind::Entity(_) => None,
+            TableKind::Archetype(_, _, relations) => relations.table::<R>(),
+        }
+    }
+}
+
+/// Where the values of a component are in a `Table`
+pub struct ColumnRef<'a, T>
+where
+    T: Component,
+{
+    kind: ColumnKind<'a, T>,
+}
+
+enum ColumnKind<'a, T>
+where
+    T: Component,
+{
+    /// The component of an `Entity` that is not in a `World`
+    Slot(&'a Slot<T::Enum>),
+    /// The column of a table component in an archetype
+    Column(&'a Column<T>),
+    /// The sparse set of a sparse component and its values
+    Sparse(&'a SparseSet<T::Enum>, &'a Column<T>),
+    /// A sparse component that no `Entity` has yet
+    Empty,
+}
+
+impl<'a, T> Clone for ColumnRef<'a, T>
+where
+    T: Component,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for ColumnRef<'a, T> where T: Component {}
+
+impl<'a, T> Clone for ColumnKind<'a, T>
+where
+    T: Component,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for ColumnKind<'a, T> where T: Component {}
+
+// Safety: a `ColumnRef` is only read together with a `Row` of the same `Table`,
+// and `Row`s can only be shared between threads when the component enum is
+// `Send` and `Sync`, and with it the type of every component
+unsafe impl<'a, T> Send for ColumnRef<'a, T> where T: Component {}
+unsafe impl<'a, T> Sync for ColumnRef<'a, T> where T: Component {}
+
+impl<'a, T> ColumnRef<'a, T>
+where
+    T: Component,
+{
+    /// Get the value of the component of an `Entity` in the `Table`
+    pub(crate) fn get(self, row: Row<'a, T::Enum>) -> Option<&'a T::Type> {
+        match self.kind {
+            ColumnKind::Slot(slot) => Some(T::enum_as_val(slot.get())),
+            ColumnKind::Column(column) => Some(column.get(row.position())),
+            ColumnKind::Sparse(set, column) => row.sparse_position(set).map(|pos| column.get(pos)),
+            ColumnKind::Empty => None,
+        }
+    }
+    /**
+    Get a mutable reference to the value of the component of an `Entity`
+    in the `Table` and mark it as changed at `tick`
+
+    # Safety
+
+    The caller must have exclusive access to the value
+    and must not create more than one reference to it.
+    */
+    pub(crate) unsafe fn get_unchecked_mut(
+        self,
+        row: Row<'a, T::Enum>,
+        tick: u64,
+    ) -> Option<&'a mut T::Type> {
+        // Safety: guaranteed by the caller
+        unsafe {
+            match self.kind {
+                ColumnKind::Slot(slot) => Some(T::enum_as_val_mut(slot.get_unchecked_mut(tick))),
+                ColumnKind::Column(column) => Some(column.get_unchecked_mut(row.position(), tick)),
+                ColumnKind::Sparse(set, column) => row
+                    .sparse_position(set)
+                    .map(|pos| column.get_unchecked_mut(pos, tick)),
+                ColumnKind::Empty => None,
+            }
+        }
+    }
+    /// Get the ticks at which the component of an `Entity` in the `Table` was added and changed
+    pub(crate) fn ticks(self, row: Row<'a, T::Enum>) -> Option<(u64, u64)> {
+        match self.kind {
+            ColumnKind::Slot(slot) => Some((slot.added(), slot.changed())),
+            ColumnKind::Column(column) => {
+                let position = row.position();
+                Some((column.added(position), column.changed(position)))
+            }
+            ColumnKind::Sparse(set, column) => row
+                .sparse_position(set)
+                .map(|pos| (column.added(pos), column.changed(pos))),
+            ColumnKind::Empty => None,
+        }
+    }
+    /// Check if an `Entity` in the `Table` has the component, without borrowing its value
+    pub(crate) fn contains(self, row: Row<'a, T::Enum>) -> bool {
+        match self.kind {
+            ColumnKind::Slot(_) | ColumnKind::Column(_) => true,
+            ColumnKind::Sparse(set, _) => row.sparse_position(set).is_some(),
+            ColumnKind::Empty => false,
+        }
+    }
+}
+
+/**
+Trait for the things that components can be borrowed from: `Entity`s,
+and the `EntityRef`s, `EntityRefMut`s, `EntityMut`s and `EntityView`s of the `Entity`s in a `World`
+
+This is what lets the closure syntax of `map!` and `tags!`
+take any of them. You do not need to implement it.
+*/
+pub trait AsRow<C> {
+    /// Get the `Row` that the components are in
+    #[doc(hidden)]
+    fn row(&self) -> Row<'_, C>;
+    /// Same as `Entity::get_many`
+    #[doc(hidden)]
+    fn fetch<'a, T>(&'a self) -> Option<T::Ref>
+    where
+        C: 'a,
+        T: ComponentSet<'a, C>,
+    {
+        self.row().fetch::<T>()
+    }
+}
+
+/**
+Trait for the things that components can be borrowed mutably from:
+`Entity`s, and the `EntityRefMut`s, `EntityMut`s and `EntityView`s of the `Entity`s in a `World`
+
+This is what lets the closure syntax of `map_mut!` take any of them.
+`EntityRef`s are `Copy`, so they can't implement it. You do not need to implement it.
+
+# Safety
+
+While `Self` is borrowed mutably, nothing else may access
+the components in the `Row` returned by `row`.
+*/
+pub unsafe trait AsRowMut<C>: AsRow<C> {
+    /// Get the tick that components obtained mutably are marked as changed at
+    #[doc(hidden)]
+    fn change_tick(&self) -> u64;
+    /// Same as `Entity::get_many_mut`
+    #[doc(hidden)]
+    fn fetch_mut<'a, T>(&'a mut self) -> Option<T::Mut>
+    where
+        C: 'a,
+        T: ComponentSet<'a, C>,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = T::DISTINCT;
+        let tick = self.change_tick();
+        let row = self.row();
+        let columns = T::columns(row.table())?;
+        // Safety: `self` is borrowed mutably, which the implementation guarantees gives
+        // exclusive access to the row, and `DISTINCT` guarantees that the components
+        // are all different
+        unsafe { T::fetch_unchecked(row, columns, tick) }
+    }
+}
+
+impl<C> AsRow<C> for Entity<C> {
+    fn row(&self) -> Row<'_, C> {
+        Row::entity(self)
+    }
+}
+
+// Safety: the components of an `Entity` outside of a `World` are owned by it
+unsafe impl<C> AsRowMut<C> for Entity<C> {
+    /// The ticks of an `Entity` outside of a `World` don't matter,
+    /// because they are all reset when it is inserted
+    fn change_tick(&self) -> u64 {
+        0
+    }
+}
+
+/**
+The values of one component in an archetype, in the order of its rows
+
+The ticks at which each value was added and last obtained mutably,
+which are used by `Added` and `Changed`, are kept in their own vectors.
+Like a `Slot`, a `Column` keeps its values and change ticks in `UnsafeCell`s
+so that queries can hand out mutable references to them through a shared reference.
+*/
+pub(crate) struct Column<T>
+where
+    T: Component,
+{
+    values: Vec<UnsafeCell<T::Type>>,
+    added: Vec<u64>,
+    changed: Vec<UnsafeCell<u64>>,
+}
+
+// Safety: the `Component` trait guarantees that the values are of the type of
+// a variant of the component enum, so they are `Send` and `Sync` when it is.
+// Values are only mutated through `&mut Column` or by `get_unchecked_mut`,
+// whose callers have exclusive access to them.
+unsafe impl<T> Send for Column<T>
+where
+    T: Component,
+    T::Enum: Send,
+{
+}
+
+unsafe impl<T> Sync for Column<T>
+where
+    T: Component,
+    T::Enum: Send + Sync,
+{
+}
+
+impl<T> Default for Column<T>
+where
+    T: Component,
+{
+    fn default() -> Self {
+        Column {
+            values: Vec::new(),
+            added: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl<T> Column<T>
+where
+    T: Component,
+{
+    pub(crate) fn get(&self, row: usize) -> &T::Type {
+        // Safety: see `get_unchecked_mut`
+        unsafe { &*self.values[row].get() }
+    }
+    /**
+    Get a mutable reference to the value in a row through a shared reference
+    and mark it as changed at `tick`
+
+    # Safety
+
+    The caller must have exclusive access to the value
+    and must not create more than one reference to it.
+    */
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_unchecked_mut(&self, row: usize, tick: u64) -> &mut T::Type {
+        // Safety: guaranteed by the caller
+        unsafe {
+            *self.changed[row].get() = tick;
+            &mut *self.values[row].get()
+        }
+    }
+    /// Get the tick at which the value in a row was added
+    pub(crate) fn added(&self, row: usize) -> u64 {
+        self.added[row]
+    }
+    /// Get the tick at which the value in a row was last obtained mutably
+    pub(crate) fn changed(&self, row: usize) -> u64 {
+        // Safety: see `get_unchecked_mut`
+        unsafe { *self.changed[row].get() }
+    }
+}
+
+/// Create an empty column for the component `T`
+pub(crate) fn new_column<T>() -> Box<dyn AnyColumn<T::Enum>>
+where
+    T: Component,
+{
+    Box::<Column<T>>::default()
+}
+
+/// A `Column` whose component is only known by its enum
+pub(crate) trait AnyColumn<C> {
+    /// Get the column as an `Any` so that it can be downcast to its `Column`
+    fn as_any(&self) -> &dyn Any;
+    /// Add a value to a new row at the end
+    fn push(&mut self, slot: Slot<C>);
+    /// Take the value out of a row, moving the last value into its place
+    fn swap_remove(&mut self, row: usize) -> Slot<C>;
+    /**
+    Clone the value in a row into the component enum, keeping its ticks
+
+    # Panics
+
+    Panics if the component's type does not implement `Clone`
+    */
+    fn clone_into_enum(&self, row: usize) -> Slot<C>;
+    /// Call `f` with every `Id` in the value in a row, see `MapEntities`
+    fn for_each_entity(&self, row: usize, f: &mut dyn FnMut(Id));
+}
+
+impl<T> AnyColumn<T::Enum> for Column<T>
+where
+    T: Component,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn push(&mut self, slot: Slot<T::Enum>) {
+        let Slot {
+            value,
+            added,
+            changed,
+            ..
+        } = slot;
+        self.values
+            .push(UnsafeCell::new(T::enum_to_val(value.into_inner())));
+        self.added.push(added);
+        self.changed.push(changed);
+    }
+    fn swap_remove(&mut self, row: usize) -> Slot<T::Enum> {
+        let value = T::new(self.values.swap_remove(row).into_inner());
+        Slot {
+            value: UnsafeCell::new(value),
+            added: self.added.swap_remove(row),
+            changed: self.changed.swap_remove(row),
+            new_column: new_column::<T>,
+        }
+    }
+    fn clone_into_enum(&self, row: usize) -> Slot<T::Enum> {
+        let value = T::clone_val(self.get(row))
+            .unwrap_or_else(|| panic!("Component {} can't be cloned", T::AS_STR));
+        Slot {
+            value: UnsafeCell::new(T::new(value)),
+            added: self.added(row),
+            changed: UnsafeCell::new(self.changed(row)),
+            new_column: new_column::<T>,
+        }
+    }
+    fn for_each_entity(&self, row: usize, f: &mut dyn FnMut(Id)) {
+        T::for_each_entity(self.get(row), f)
+    }
+}
+
+/**
+The `Entity`s that have exactly the same table components
+
+Every component has its own `Column` of values, and the `Entity` in
+a row of the archetype has its values in that row of every column.
+*/
+pub(crate) struct Archetype<C> {
+    /// The names of the components in sorted order
+    components: Box<[&'static str]>,
+    /// The values of the components, in the same order as `components`
+    columns: Box<[Box<dyn AnyColumn<C>>]>,
+    /// The `Id` of the `Entity` in each row
+    ids: Vec<Id>,
+    /// The uuid of the `Entity` in each row
+    #[cfg(feature = "f_uuid")]
+    uuids: Vec<Uuid>,
+}
+
+// Safety: the columns only hold values of the types of the components of `C`,
+// so they can be sent and shared between threads when `C` can
+unsafe impl<C: Send> Send for Archetype<C> {}
+unsafe impl<C: Send + Sync> Sync for Archetype<C> {}
+
+impl<C> Archetype<C> {
+    /// Create an empty archetype for the table components of an `Entity`
+    pub(crate) fn new(entity: &Entity<C>) -> Archetype<C> {
+        Archetype {
+            components: entity.names().collect(),
+            columns: entity
+                .components
+                .iter()
+                .map(|(_, slot)| (slot.new_column)())
+                .collect(),
+            ids: Vec::new(),
+            #[cfg(feature = "f_uuid")]
+            uuids: Vec::new(),
+        }
+    }
+    /// Get the column of a component
+    pub(crate) fn column<T>(&self) -> Option<&Column<T>>
+    where
+        T: Component<Enum = C>,
+    {
+        let i = self.components.binary_search(&T::AS_STR).ok()?;
+        self.columns[i].as_any().downcast_ref()
+    }
+    /// Get the archetype as a `Table`, given the `World`'s sparse sets and relations
+    pub(crate) fn table<'a>(
+        &'a self,
+        sparse: &'a SparseSets<C>,
+        relations: &'a Relations,
+    ) -> Table<'a, C> {
+        Table {
+            kind: TableKind::Archetype(self, sparse, relations),
+        }
+    }
+    /// Get the number of `Entity`s in the archetype
+    pub(crate) fn len(&self) -> usize {
+        self.ids.len()
+    }
+    /// Get the `Id`s of the `Entity`s in the archetype, in the order of their rows
+    pub(crate) fn ids(&self) -> &[Id] {
+        &self.ids
+    }
+    /// Get the uuid of the `Entity` in a row
+    #[cfg(feature = "f_uuid")]
+    pub(crate) fn uuid(&self, row: usize) -> Uuid {
+        self.uuids[row]
+    }
+    /// Get the components of the `Entity` in a row, given the `World`'s sparse sets and relations
+    pub(crate) fn row<'a>(
+        &'a self,
+        row: usize,
+        sparse: &'a SparseSets<C>,
+        relations: &'a Relations,
+    ) -> Row<'a, C> {
+        Row {
+            kind: RowKind::Table {
+                archetype: self,
+                row,
+                sparse,
+                relations,
+            },
+        }
+    }
+    /**
+    Add an `Entity` with the archetype's components to a new row and return the row
+
+    Its sparse components must already have been moved to the `World`'s sparse sets.
+    */
+    pub(crate) fn push(&mut self, id: Id, entity: Entity<C>) -> usize {
+        debug_assert!(entity.names().eq(self.components.iter().copied()));
+        debug_assert!(entity.sparse.is_empty());
+        for (column, (_, slot)) in self.columns.iter_mut().zip(entity.components) {
+            column.push(slot);
+        }
+        self.ids.push(id);
+        #[cfg(feature = "f_uuid")]
+        self.uuids.push(entity.uuid);
+        self.ids.len() - 1
+    }
+    /**
+    Take the `Entity` in a row out of the archetype, without its sparse components
+
+    The last `Entity` is moved into its row, so the caller has to update its location.
+    */
+    pub(crate) fn swap_remove(&mut self, row: usize) -> Entity<C> {
+        let components = self
+            .components
+            .iter()
+            .zip(self.columns.iter_mut())
+            .map(|(name, column)| (*name, column.swap_remove(row)))
+            .collect();
+        self.ids.swap_remove(row);
+        Entity {
+            #[cfg(feature = "f_uuid")]
+            uuid: self.uuids.swap_remove(row),
+            components,
+            sparse: Vec::new(),
+        }
+    }
+    /// Clone the table components of the `Entity` in a row
+    ///
+    /// See `AnyColumn::clone_into_enum`.
+    pub(crate) fn clone_row(&self, row: usize) -> Vec<(&'static str, Slot<C>)> {
+        self.components
+            .iter()
+            .zip(self.columns.iter())
+            .map(|(name, column)| (*name, column.clone_into_enum(row)))
+            .collect()
+    }
+    /// Call `f` with the name of every component of the `Entity` in a row
+    /// and every `Id` in its value
+    pub(crate) fn for_each_entity(&self, row: usize, f: &mut dyn FnMut(&'static str, Id)) {
+        for (name, column) in self.components.iter().zip(self.columns.iter()) {
+            column.for_each_entity(row, &mut |id| f(name, id));
+        }
+    }
+}
+
+/// The sparse sets of a `World`, by component name
+pub(crate) type SparseSets<C> = BTreeMap<&'static str, SparseSet<C>>;
+
+/**
+The values of a sparse component
+
+The values are stored densely in a `Column`, and found by the index
+of their `Entity`'s `Id` through a sparse array.
+*/
+pub(crate) struct SparseSet<C> {
+    /// The `Id` index of the `Entity` of each value
+    dense: Vec<u32>,
+    /// The values, in the same order as `dense`
+    column: Box<dyn AnyColumn<C>>,
+    /// The position in `dense` of each `Id` index, or `NONE`
+    sparse: Vec<u32>,
+}
+
+// Safety: see `Archetype`
+unsafe impl<C: Send> Send for SparseSet<C> {}
+unsafe impl<C: Send + Sync> Sync for SparseSet<C> {}
+
+impl<C> SparseSet<C> {
+    const NONE: u32 = u32::MAX;
+    /// Create an empty sparse set whose values are stored in the column created by `new_column`
+    pub(crate) fn new(new_column: fn() -> Box<dyn AnyColumn<C>>) -> SparseSet<C> {
+        SparseSet {
+            dense: Vec::new(),
+            column: new_column(),
+            sparse: Vec::new(),
+        }
+    }
+    /// Get the `Id` indices of the `Entity`s that have the component
+    pub(crate) fn indices(&self) -> &[u32] {
+        &self.dense
+    }
+    /// Get the column of the values
+    pub(crate) fn column<T>(&self) -> Option<&Column<T>>
+    where
+        T: Component<Enum = C>,
+    {
+        self.column.as_any().downcast_ref()
+    }
+    /// Get the position in the column of the value of the `Entity` with the given `Id` index
+    pub(crate) fn position(&self, index: u32) -> Option<usize> {
+        match self.sparse.get(index as usize) {
+            Some(&pos) if pos != SparseSet::<C>::NONE => Some(pos as usize),
+            _ => None,
+        }
+    }
+    /// Check if the `Entity` with the given `Id` index has a value
+    pub(crate) fn contains(&self, index: u32) -> bool {
+        self.position(index).is_some()
+    }
+    /// Add the value of the `Entity` with the given `Id` index, which must not have one
+    pub(crate) fn insert(&mut self, index: u32, slot: Slot<C>) {
+        debug_assert!(self.position(index).is_none());
+        let i = index as usize;
+        if i >= self.sparse.len() {
+            self.sparse.resize(i + 1, SparseSet::<C>::NONE);
+        }
+        self.sparse[i] = self.dense.len() as u32;
+        self.dense.push(index);
+        self.column.push(slot);
+    }
+    /// Remove the value of the `Entity` with the given `Id` index
+    pub(crate) fn remove(&mut self, index: u32) -> Option<Slot<C>> {
+        let pos = self.position(index)?;
+        self.dense.swap_remove(pos);
+        let slot = self.column.swap_remove(pos);
+        if let Some(&moved) = self.dense.get(pos) {
+            self.sparse[moved as usize] = pos as u32;
+        }
+        self.sparse[index as usize] = SparseSet::<C>::NONE;
+        Some(slot)
+    }
+    /// Clone the value of the `Entity` with the given `Id` index, if it has one
+    ///
+    /// See `AnyColumn::clone_into_enum`.
+    pub(crate) fn clone_into_enum(&self, index: u32) -> Option<Slot<C>> {
+        self.position(index)
+            .map(|pos| self.column.clone_into_enum(pos))
+    }
+    /// Call `f` with every `Id` in the value of the `Entity` with the given `Id` index,
+    /// if it has one
+    pub(crate) fn for_each_entity(&self, index: u32, f: &mut dyn FnMut(Id)) {
+        if let Some(pos) = self.position(index) {
+            self.column.for_each_entity(pos, f);
+        }
+    }
+}
@@ -0,0 +1,2196 @@
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+    sync::{
+        atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+#[cfg(feature = "f_rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "f_serde")]
+use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "f_uuid")]
+use uuid::Uuid;
+
+use crate::{
+    hierarchy::Hierarchy,
+    hook::{HookKind, Hooks},
+    observer::Observers,
+    query::Either,
+    relation::Relations,
+    resource::Resources,
+    storage::{new_column, Archetype, SparseSet, SparseSets},
+    AsRow, AsRowMut, Commands, Component, ComponentSet, DanglingReference, Entity, EventReader,
+    Events, Filter, Id, Relation, Row, Slot, Storage,
+};
+#[cfg(feature = "f_serde")]
+use crate::{serialization::OrderedMap, SerdeComponents};
+
+/**
+The world of the ECS
+
+`Entity`s with the same set of components are stored together
+in an archetype, which has a column of values for each component.
+Iterating over the `Entity`s that have some components only visits
+the archetypes that have all of them.
+
+Indexing a `World` with an `Id` gives an `EntityView`,
+so `world[id][Position] = value` sets a component.
+
+`Entity`s can also be arranged in trees with `World::set_parent`,
+and removed together with their descendants with `World::remove_recursive`.
+
+Hooks registered with `World::on_add`, `World::on_insert` and `World::on_remove`
+run whenever a component is added to or removed from an `Entity` in the `World`,
+and observers registered with `World::observe` run when an event is triggered
+on an `Entity` with `World::trigger`.
+*/
+pub struct World<C> {
+    /// Tells `Query`s which `World` their cached archetypes belong to
+    world_id: u64,
+    entries: Vec<Entry>,
+    /// What indexing the `World` gives, one for each entry
+    views: Vec<EntityView<C>>,
+    free: Vec<u32>,
+    /// The number of `Id`s handed out by `World::reserve_id` since the last `World::flush`
+    pending: AtomicUsize,
+    archetypes: Vec<Archetype<C>>,
+    archetype_ids: HashMap<Box<[&'static str]>, usize>,
+    sparse: SparseSets<C>,
+    resources: Resources,
+    /// Updates the `Events` resource of each event type that has been added
+    event_updates: HashMap<TypeId, fn(&mut Resources)>,
+    hierarchy: Hierarchy,
+    relations: Relations,
+    hooks: Hooks<C>,
+    observers: Observers<C>,
+    len: usize,
+    /// The tick that changes are marked with
+    tick: u64,
+    /// The tick that `Added` and `Changed` compare against
+    last_run: u64,
+    /// The `Id`s that each tracked component was removed from, and the ticks they were removed at
+    removed: HashMap<&'static str, Vec<(Id, u64)>>,
+    #[cfg(feature = "f_uuid")]
+    uuids: HashMap<Uuid, Id>,
+}
+
+/// Update the `Events` resource for events of type `E`, if there is one
+fn update_events<E>(resources: &mut Resources)
+where
+    E: 'static,
+{
+    if let Some(events) = resources.get_mut::<Events<E>>() {
+        events.update();
+    }
+}
+
+/// Whether an `Id` refers to an `Entity` in a `World`, as returned by `World::status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdStatus {
+    /// The `Entity` is in the `World`
+    Alive,
+    /**
+    The `Id`'s index has a newer generation in the `World`
+
+    This is what an `Id` of a removed `Entity` looks like, but an `Id` from a different
+    `World` can look the same, so it does not prove that the `Entity` ever existed.
+    */
+    Removed,
+    /// The `World` has not handed out an `Id` with this index and generation yet
+    NeverExisted,
+}
+
+/// A slot for an `Id` index that may point to an `Entity`
+struct Entry {
+    generation: u32,
+    location: Option<Location>,
+    /// The `Id` was reserved by `World::reserve_id` and its `Entity` is not inserted yet
+    reserved: bool,
+}
+
+/// Where an `Entity` is stored
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    archetype: usize,
+    row: usize,
+}
+
+impl<C> Default for World<C> {
+    fn default() -> Self {
+        World::new()
+    }
+}
+
+impl<C> World<C> {
+    /// Create a new `World`
+    pub fn new() -> World<C> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        World {
+            world_id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            entries: Vec::new(),
+            views: Vec::new(),
+            free: Vec::new(),
+            pending: AtomicUsize::new(0),
+            archetypes: Vec::new(),
+            archetype_ids: HashMap::new(),
+            sparse: BTreeMap::new(),
+            resources: Resources::default(),
+            event_updates: HashMap::new(),
+            hierarchy: Hierarchy::default(),
+            relations: Relations::default(),
+            hooks: Hooks::default(),
+            observers: Observers::default(),
+            len: 0,
+            tick: 1,
+            last_run: 0,
+            removed: HashMap::new(),
+            #[cfg(feature = "f_uuid")]
+            uuids: HashMap::new(),
+        }
+    }
+    /// Add an `Entity` to the `World`
+    pub fn insert(&mut self, entity: Entity<C>) -> Id {
+        let id = self.reserve();
+        let mut commands = Commands::new();
+        self.insert_at(id, entity, &mut commands);
+        commands.apply(self);
+        id
+    }
+    /// Allocate an `Id` for an `Entity` that will be inserted with `World::insert_at`
+    pub(crate) fn reserve(&mut self) -> Id {
+        self.flush();
+        if let Some(index) = self.free.pop() {
+            Id {
+                index,
+                generation: self.entries[index as usize].generation,
+            }
+        } else {
+            self.push_entry(Entry {
+                generation: 0,
+                location: None,
+                reserved: false,
+            });
+            Id {
+                index: (self.entries.len() - 1) as u32,
+                generation: 0,
+            }
+        }
+    }
+    /**
+    Reserve an `Id` for an `Entity` that will be spawned by `Commands`
+
+    This only needs a shared reference, so it can be called while the `World`
+    is being iterated, or from several threads at once. The `Id` is not in
+    the `World` until an `Entity` is inserted under it with `Commands::spawn_reserved`.
+    If that never happens, the `Id` is not handed out again.
+    */
+    pub fn reserve_id(&self) -> Id {
+        let n = self.pending.fetch_add(1, Ordering::Relaxed);
+        match n.checked_sub(self.free.len()) {
+            // Hand out the free indices in the order `World::reserve` would
+            None => {
+                let index = self.free[self.free.len() - 1 - n];
+                Id {
+                    index,
+                    generation: self.entries[index as usize].generation,
+                }
+            }
+            Some(new) => Id {
+                index: (self.entries.len() + new) as u32,
+                generation: 0,
+            },
+        }
+    }
+    /// Mark the `Id`s handed out by `World::reserve_id` as reserved
+    fn flush(&mut self) {
+        let pending = std::mem::replace(self.pending.get_mut(), 0);
+        let reused = pending.min(self.free.len());
+        for index in self.free.drain(self.free.len() - reused..) {
+            self.entries[index as usize].reserved = true;
+        }
+        for _ in reused..pending {
+            self.push_entry(Entry {
+                generation: 0,
+                location: None,
+                reserved: true,
+            });
+        }
+    }
+    /**
+    Insert an `Entity` under an `Id` reserved with `World::reserve_id`
+
+    # Panics
+
+    Panics if the `Id` is not reserved in this `World`, i.e. if it was reserved
+    in another `World` or if an `Entity` was already inserted under it.
+    */
+    pub(crate) fn insert_reserved(&mut self, id: Id, entity: Entity<C>) {
+        self.flush();
+        match self.entries.get_mut(id.index as usize) {
+            Some(entry) if entry.reserved && entry.generation == id.generation => {
+                entry.reserved = false;
+                let mut commands = Commands::new();
+                self.insert_at(id, entity, &mut commands);
+                commands.apply(self);
+            }
+            _ => panic!("Entity id {} is not reserved in this world", id),
+        }
+    }
+    /**
+    Put an `Entity` in the `World` under an `Id` whose index is not in use
+
+    The `on_add` and `on_insert` hooks of its components record their commands in `commands`.
+    */
+    pub(crate) fn insert_at(&mut self, id: Id, mut entity: Entity<C>, commands: &mut Commands<C>) {
+        entity.stamp(self.tick);
+        #[cfg(feature = "f_uuid")]
+        self.uuids.insert(entity.uuid, id);
+        let location = self.place(id, entity);
+        self.entries[id.index as usize].location = Some(location);
+        self.len += 1;
+        let row = self.entity_ref(id.index).table_row();
+        self.hooks.run_all(HookKind::Add, id, row, commands);
+        self.hooks.run_all(HookKind::Insert, id, row, commands);
+    }
+    /// Removes the `Entity` with the given id
+    ///
+    /// Any copies of the id become stale and will no longer
+    /// refer to an `Entity` in this `World`. The `Entity` is detached
+    /// from its parent, and its children are left without a parent.
+    /// Use `World::remove_recursive` to remove the children as well.
+    /// The observers added with `World::observe_entity` for it are removed.
+    ///
+    /// Its `Relation`s are removed, and the ones that target it are
+    /// handled according to their `OnTargetRemoved`.
+    pub fn remove(&mut self, id: Id) -> Option<Entity<C>> {
+        let mut commands = Commands::new();
+        let mut cascade = Vec::new();
+        let entity = self.remove_one(id, &mut commands, &mut cascade)?;
+        // Chains of relations can be long, so they are followed
+        // with a worklist instead of recursion
+        while let Some(source) = cascade.pop() {
+            self.remove_one(source, &mut commands, &mut cascade);
+        }
+        commands.apply(self);
+        Some(entity)
+    }
+    /**
+    Remove a single `Entity`
+
+    The commands of its `on_remove` hooks are recorded in `commands`, and the sources of
+    the `Relation`s that are removed along with it are pushed to `cascade`.
+    */
+    fn remove_one(
+        &mut self,
+        id: Id,
+        commands: &mut Commands<C>,
+        cascade: &mut Vec<Id>,
+    ) -> Option<Entity<C>> {
+        let location = self.location(id)?;
+        self.hooks.run_all(
+            HookKind::Remove,
+            id,
+            self.entity_ref(id.index).table_row(),
+            commands,
+        );
+        let mut entity = self.take(location);
+        for (name, set) in self.sparse.iter_mut() {
+            if let Some(slot) = set.remove(id.index) {
+                entity.sparse.push((name, slot));
+            }
+        }
+        for name in entity.names().chain(entity.sparse_names()) {
+            self.record_removed(name, id);
+        }
+        let entry = &mut self.entries[id.index as usize];
+        entry.location = None;
+        entry.generation = entry.generation.wrapping_add(1);
+        self.flush();
+        self.free.push(id.index);
+        self.len -= 1;
+        self.hierarchy.remove(id);
+        self.observers.remove_entity(id);
+        cascade.extend(self.relations.remove_entity(id));
+        #[cfg(feature = "f_uuid")]
+        self.uuids.remove(&entity.uuid);
+        Some(entity)
+    }
+    /// Get the number of `Entity`s in the `World`
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Check if the `World` has no `Entity`s
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Check if the `World` contains an `Entity` with the given `Id`
+    pub fn contains(&self, id: Id) -> bool {
+        self.location(id).is_some()
+    }
+    /**
+    Check whether an `Id` refers to an `Entity` in the `World`,
+    and if not, whether its index has a newer generation
+
+    `World::get` returns `None` in both cases. `Id`s don't know which
+    `World` they come from, so see `IdStatus` for what this can tell.
+    */
+    pub fn status(&self, id: Id) -> IdStatus {
+        match self.entries.get(id.index as usize) {
+            Some(entry) if entry.generation == id.generation && entry.location.is_some() => {
+                IdStatus::Alive
+            }
+            Some(entry) if id.generation < entry.generation => IdStatus::Removed,
+            _ => IdStatus::NeverExisted,
+        }
+    }
+    /**
+    Find the components that refer to `Entity`s that are not in the `World`
+
+    This goes through every component of every `Entity`, so it is meant
+    for debugging rather than for running every frame. The `Id`s are found
+    through the `MapEntities` impls of the components' types.
+    */
+    pub fn dangling_references(&self) -> Vec<DanglingReference> {
+        let mut references = Vec::new();
+        for archetype in &self.archetypes {
+            for (row, &source) in archetype.ids().iter().enumerate() {
+                archetype.for_each_entity(row, &mut |component, target| {
+                    references.push((source, component, target))
+                });
+            }
+        }
+        for (&component, set) in &self.sparse {
+            for &index in set.indices() {
+                let source = self.entity_ref(index).id();
+                set.for_each_entity(index, &mut |target| {
+                    references.push((source, component, target))
+                });
+            }
+        }
+        references
+            .into_iter()
+            .filter_map(|(source, component, target)| {
+                let status = self.status(target);
+                if status == IdStatus::Alive {
+                    return None;
+                }
+                Some(DanglingReference {
+                    source,
+                    component,
+                    target,
+                    status,
+                })
+            })
+            .collect()
+    }
+    /**
+    Clone the components of the `Entity` with the given `Id` into an `Entity`
+    that is not in a `World`
+
+    # Panics
+
+    Panics if the type of one of the components does not implement `Clone`,
+    which can only happen if the component enum implements `Clone` by hand
+    */
+    pub fn clone_entity(&self, id: Id) -> Option<Entity<C>>
+    where
+        C: Clone,
+    {
+        let location = self.location(id)?;
+        let archetype = &self.archetypes[location.archetype];
+        let mut entity = Entity::new();
+        #[cfg(feature = "f_uuid")]
+        {
+            entity.uuid = archetype.uuid(location.row);
+        }
+        entity.components = archetype.clone_row(location.row);
+        for (&name, set) in &self.sparse {
+            if let Some(slot) = set.clone_into_enum(id.index) {
+                entity.sparse.push((name, slot));
+            }
+        }
+        Some(entity)
+    }
+    /// Iterates through all `Entities` in the `World`
+    pub fn iter(&self) -> Iter<'_, C> {
+        Iter {
+            world: self,
+            archetypes: self.archetypes.iter(),
+            ids: [].iter(),
+        }
+    }
+    /// Iterates through all `Entities` in the `World` with handles that can change their components
+    pub fn iter_mut(&mut self) -> IterMut<'_, C> {
+        IterMut {
+            world: self,
+            archetypes: self.archetypes.iter(),
+            ids: [].iter(),
+        }
+    }
+    /// Get a reference to the `Entity` with the given `Id`
+    pub fn get(&self, id: Id) -> Option<EntityRef<'_, C>> {
+        self.location(id).map(|_| self.entity_ref(id.index))
+    }
+    /**
+    Get a reference to the `Entity` with the given `Id`
+
+    # Panics
+
+    Panics if the `Entity` is not in the `World`
+    */
+    pub fn entity(&self, id: Id) -> EntityRef<'_, C> {
+        match self.get(id) {
+            Some(entity) => entity,
+            None => self.missing(id),
+        }
+    }
+    /**
+    Get a mutable handle to the `Entity` with the given `Id`
+
+    Adding or removing components must go through the handle so that
+    the `Entity` can be moved to the archetype for its new set of components.
+    */
+    pub fn get_mut(&mut self, id: Id) -> Option<EntityMut<'_, C>> {
+        self.location(id)?;
+        Some(EntityMut {
+            world: self,
+            id,
+            commands: Commands::new(),
+        })
+    }
+    /**
+    Get a mutable handle to the `Entity` with the given `Id`
+
+    # Panics
+
+    Panics if the `Entity` is not in the `World`
+    */
+    pub fn entity_mut(&mut self, id: Id) -> EntityMut<'_, C> {
+        if self.location(id).is_none() {
+            self.missing(id)
+        }
+        EntityMut {
+            world: self,
+            id,
+            commands: Commands::new(),
+        }
+    }
+    /// Get the `Id` of the `Entity` with the given uuid
+    #[cfg(feature = "f_uuid")]
+    pub fn find_uuid(&self, uuid: Uuid) -> Option<Id> {
+        self.uuids.get(&uuid).copied()
+    }
+    /**
+    Make the `Entity` with the `Id` `parent` the parent of the one with the `Id` `child`
+
+    An `Entity` has at most one parent, so `child` is first detached from
+    its old parent if it had one. Returns `false` and does nothing if either
+    `Entity` is not in the `World`, or if `parent` is `child` or one of its descendants.
+    */
+    pub fn set_parent(&mut self, child: Id, parent: Id) -> bool {
+        if !self.contains(child)
+            || !self.contains(parent)
+            || self.hierarchy.is_ancestor(child, parent)
+        {
+            return false;
+        }
+        self.hierarchy.set_parent(child, parent);
+        true
+    }
+    /// Detach the `Entity` with the given `Id` from its parent and return the parent's `Id`
+    pub fn remove_parent(&mut self, child: Id) -> Option<Id> {
+        self.hierarchy.remove_parent(child)
+    }
+    /// Get the `Id` of the parent of the `Entity` with the given `Id`
+    pub fn parent(&self, id: Id) -> Option<Id> {
+        self.hierarchy.parent(id)
+    }
+    /// Get the `Id`s of the children of the `Entity` with the given `Id`
+    /// in the order they were added
+    pub fn children(&self, id: Id) -> &[Id] {
+        self.hierarchy.children(id)
+    }
+    /// Iterates through the `Id`s of the parent of the `Entity` with the given `Id`,
+    /// the parent's parent and so on
+    pub fn ancestors(&self, id: Id) -> impl Iterator<Item = Id> + '_ {
+        std::iter::successors(self.parent(id), move |&id| self.parent(id))
+    }
+    /// Iterates depth-first through the `Id`s of the children of the `Entity`
+    /// with the given `Id`, their children and so on
+    pub fn descendants(&self, id: Id) -> impl Iterator<Item = Id> + '_ {
+        let mut stack: Vec<Id> = self.children(id).iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            stack.extend(self.children(id).iter().rev());
+            Some(id)
+        })
+    }
+    /**
+    Removes the `Entity` with the given `Id` and all of its descendants
+
+    Returns the removed `Entity`s, starting with the one with the given `Id`
+    and followed by its descendants in the order of `World::descendants`.
+    */
+    pub fn remove_recursive(&mut self, id: Id) -> Vec<Entity<C>> {
+        if !self.contains(id) {
+            return Vec::new();
+        }
+        let ids: Vec<Id> = std::iter::once(id).chain(self.descendants(id)).collect();
+        ids.into_iter().filter_map(|id| self.remove(id)).collect()
+    }
+    /**
+    Add the relation `R` from the `Entity` with the `Id` `source`
+    to the one with the `Id` `target`
+
+    Returns `false` and does nothing if either `Entity` is not
+    in the `World`, or if the relation already exists.
+    */
+    pub fn relate<R>(&mut self, source: Id, target: Id) -> bool
+    where
+        R: Relation,
+    {
+        self.contains(source) && self.contains(target) && self.relations.insert::<R>(source, target)
+    }
+    /// Remove the relation `R` from `source` to `target` and return `false` if it didn't exist
+    pub fn unrelate<R>(&mut self, source: Id, target: Id) -> bool
+    where
+        R: Relation,
+    {
+        self.relations.remove::<R>(source, target)
+    }
+    /// Get the `Id`s that the `Entity` with the `Id` `source` has the relation `R` with
+    /// in the order the relations were added
+    pub fn targets<R>(&self, source: Id) -> &[Id]
+    where
+        R: Relation,
+    {
+        self.relations.targets::<R>(source)
+    }
+    /// Get the `Id`s of the `Entity`s that have the relation `R`
+    /// with the one with the `Id` `target`
+    pub fn sources<R>(&self, target: Id) -> &[Id]
+    where
+        R: Relation,
+    {
+        self.relations.sources::<R>(target)
+    }
+    /**
+    Iterates through the sources and targets of the relations `R`
+    whose target is no longer in the `World`
+
+    Only relations with `OnTargetRemoved::Keep` are left pointing at removed
+    `Entity`s. They stay until they are removed with `World::unrelate`
+    or their source is removed from the `World`.
+    */
+    pub fn dangling<R>(&self) -> impl Iterator<Item = (Id, Id)> + '_
+    where
+        R: Relation,
+    {
+        self.relations
+            .pairs::<R>()
+            .filter(move |&(_, target)| !self.contains(target))
+    }
+    /**
+    Add a resource to the `World`
+
+    A resource is a value that is not attached to any `Entity`, such as the
+    time step or the score. There can only be one resource of each type.
+    The old resource of the same type is returned if there was one.
+    */
+    pub fn insert_resource<R>(&mut self, value: R) -> Option<R>
+    where
+        R: Send + Sync + 'static,
+    {
+        self.resources.insert(value)
+    }
+    /// Removes the resource of the given type
+    pub fn remove_resource<R>(&mut self) -> Option<R>
+    where
+        R: 'static,
+    {
+        self.resources.remove::<R>()
+    }
+    /// Check if the `World` has a resource of the given type
+    pub fn contains_resource<R>(&self) -> bool
+    where
+        R: 'static,
+    {
+        self.resources.contains::<R>()
+    }
+    /// Get a reference to the resource of the given type
+    pub fn resource<R>(&self) -> Option<&R>
+    where
+        R: 'static,
+    {
+        self.resources.get::<R>()
+    }
+    /// Get a mutable reference to the resource of the given type
+    pub fn resource_mut<R>(&mut self) -> Option<&mut R>
+    where
+        R: 'static,
+    {
+        self.resources.get_mut::<R>()
+    }
+    /**
+    Add the `Events` resource for events of type `E` if there isn't one
+
+    `World::send_event` does this automatically. Once added, the events
+    are updated by `World::update_events`.
+    */
+    pub fn add_event<E>(&mut self)
+    where
+        E: Send + Sync + 'static,
+    {
+        if !self.resources.contains::<Events<E>>() {
+            self.resources.insert(Events::<E>::new());
+        }
+        self.event_updates
+            .entry(TypeId::of::<E>())
+            .or_insert(update_events::<E>);
+    }
+    /// Send an event that can be read with an `EventReader`
+    pub fn send_event<E>(&mut self, event: E)
+    where
+        E: Send + Sync + 'static,
+    {
+        self.add_event::<E>();
+        self.resources
+            .get_mut::<Events<E>>()
+            .expect("Events were just added")
+            .send(event);
+    }
+    /// Iterates through the events of type `E` that the reader has not read yet
+    pub fn read_events<'a, E>(
+        &'a self,
+        reader: &mut EventReader<E>,
+    ) -> impl Iterator<Item = &'a E> + 'a
+    where
+        E: 'static,
+    {
+        self.resources
+            .get::<Events<E>>()
+            .map(|events| reader.read(events))
+            .into_iter()
+            .flatten()
+    }
+    /**
+    Drop the events sent before the previous call, and keep the ones sent since
+
+    A `Schedule` calls this at the end of every run.
+    */
+    pub fn update_events(&mut self) {
+        for update in self.event_updates.values() {
+            update(&mut self.resources);
+        }
+    }
+    /**
+    Register a hook that runs when the component `T` is added to an `Entity` that didn't have it
+
+    This includes `Entity`s inserted into the `World` with the component,
+    such as ones built with `entity!`. The hook is given the `Entity`'s `Id`
+    and the component's value, and can record changes to the `World` in
+    a `Commands`, which are applied once the change that ran the hook is done.
+    For changes made through an `EntityMut`, that is when it is dropped.
+
+    # Example
+    ```
+    use eks::*;
+    use std::collections::HashMap;
+
+    component! { Position: (i32, i32) }
+
+    /// Finds the `Entity` at each position
+    #[derive(Default)]
+    struct Grid(HashMap<(i32, i32), Id>);
+
+    let mut world = World::new();
+    world.insert_resource(Grid::default());
+    world.on_insert::<Position, _>(|id, &position, commands| {
+        commands.push(move |world| {
+            world.resource_mut::<Grid>().unwrap().0.insert(position, id);
+        });
+    });
+    world.on_remove::<Position, _>(|_, &position, commands| {
+        commands.push(move |world| {
+            world.resource_mut::<Grid>().unwrap().0.remove(&position);
+        });
+    });
+
+    let id = world.insert(entity! { Position: (1, 2) });
+    assert_eq!(Some(&id), world.resource::<Grid>().unwrap().0.get(&(1, 2)));
+
+    world.get_mut(id).unwrap().remove::<Position>();
+    assert!(world.resource::<Grid>().unwrap().0.is_empty());
+    ```
+    */
+    pub fn on_add<T, F>(&mut self, hook: F)
+    where
+        T: Component<Enum = C> + 'static,
+        F: Fn(Id, &T::Type, &mut Commands<C>) + Send + Sync + 'static,
+    {
+        self.add_hook::<T, F>(HookKind::Add, hook);
+    }
+    /**
+    Register a hook that runs whenever a value of the component `T` is added to an `Entity`
+
+    Unlike `World::on_add`, this also runs when the value replaces an old one,
+    after the `on_add` hooks if the `Entity` didn't have the component.
+    */
+    pub fn on_insert<T, F>(&mut self, hook: F)
+    where
+        T: Component<Enum = C> + 'static,
+        F: Fn(Id, &T::Type, &mut Commands<C>) + Send + Sync + 'static,
+    {
+        self.add_hook::<T, F>(HookKind::Insert, hook);
+    }
+    /**
+    Register a hook that runs when the component `T` is removed from an `Entity`
+
+    This includes `Entity`s removed from the `World` while they have the component.
+    The hook runs before the removal, so it is given the value that is removed.
+    */
+    pub fn on_remove<T, F>(&mut self, hook: F)
+    where
+        T: Component<Enum = C> + 'static,
+        F: Fn(Id, &T::Type, &mut Commands<C>) + Send + Sync + 'static,
+    {
+        self.add_hook::<T, F>(HookKind::Remove, hook);
+    }
+    fn add_hook<T, F>(&mut self, kind: HookKind, hook: F)
+    where
+        T: Component<Enum = C> + 'static,
+        F: Fn(Id, &T::Type, &mut Commands<C>) + Send + Sync + 'static,
+    {
+        self.hooks.insert(
+            T::AS_STR,
+            kind,
+            Box::new(move |id, row, commands| {
+                if let Some(value) = row.get::<T>() {
+                    hook(id, value, commands)
+                }
+            }),
+        );
+    }
+    /**
+    Register an observer that runs whenever an event of type `E` is triggered on any `Entity`
+
+    The observer is given the `Id` of the target and the event. It can change
+    the `World` directly, including triggering more events, and record changes
+    in a `Commands`, which are applied once every observer of the event has run.
+    Observers registered while an event is being handled don't see that event.
+
+    # Example
+    ```
+    use eks::*;
+
+    component! { Health: i32, Shield: i32 }
+
+    struct Damage(i32);
+    struct Died;
+
+    let mut world = World::new();
+    world.observe(|target, damage: &Damage, world: &mut World<Comp>, commands| {
+        let mut entity = world.get_mut(target).unwrap();
+        let damage = match entity.get_mut::<Shield>() {
+            Some(shield) => {
+                let absorbed = damage.0.min(*shield);
+                *shield -= absorbed;
+                damage.0 - absorbed
+            }
+            None => damage.0,
+        };
+        if let Some(health) = entity.get_mut::<Health>() {
+            *health -= damage;
+            if *health <= 0 {
+                commands.trigger(target, Died);
+            }
+        }
+    });
+    world.observe(|target, _: &Died, _: &mut World<Comp>, commands| {
+        commands.despawn(target);
+    });
+
+    let knight = world.insert(entity! { Health: 10, Shield: 5 });
+    let peasant = world.insert(entity! { Health: 10 });
+    world.trigger(knight, Damage(12));
+    world.trigger(peasant, Damage(12));
+
+    assert_eq!(3, world[knight][Health]);
+    assert!(!world.contains(peasant));
+    ```
+    */
+    pub fn observe<E, F>(&mut self, observer: F)
+    where
+        E: 'static,
+        F: Fn(Id, &E, &mut World<C>, &mut Commands<C>) + Send + Sync + 'static,
+    {
+        self.add_observer::<E, F>(None, observer);
+    }
+    /**
+    Register an observer that runs whenever an event of type `E` is triggered
+    on the `Entity` with the `Id` `target`
+
+    It runs before the observers registered with `World::observe`, and is removed
+    when the `Entity` is removed from the `World`. Returns `false` and does nothing
+    if the `Entity` is not in the `World`.
+    */
+    pub fn observe_entity<E, F>(&mut self, target: Id, observer: F) -> bool
+    where
+        E: 'static,
+        F: Fn(Id, &E, &mut World<C>, &mut Commands<C>) + Send + Sync + 'static,
+    {
+        if !self.contains(target) {
+            return false;
+        }
+        self.add_observer::<E, F>(Some(target), observer);
+        true
+    }
+    fn add_observer<E, F>(&mut self, target: Option<Id>, observer: F)
+    where
+        E: 'static,
+        F: Fn(Id, &E, &mut World<C>, &mut Commands<C>) + Send + Sync + 'static,
+    {
+        self.observers.insert(
+            TypeId::of::<E>(),
+            target,
+            Arc::new(move |target, event, world, commands| {
+                let event = event
+                    .downcast_ref::<E>()
+                    .expect("Observer of wrong event type");
+                observer(target, event, world, commands)
+            }),
+        );
+    }
+    /**
+    Trigger an event on the `Entity` with the `Id` `target`, running its observers
+
+    Unlike events sent with `World::send_event`, triggered events are handled
+    right away and are not stored. Does nothing if the `Entity` is not in the `World`,
+    and the remaining observers are skipped once an observer removes it.
+    */
+    pub fn trigger<E>(&mut self, target: Id, event: E)
+    where
+        E: 'static,
+    {
+        let mut commands = Commands::new();
+        for observer in self.observers.get(TypeId::of::<E>(), target) {
+            if !self.contains(target) {
+                break;
+            }
+            observer(target, &event, self, &mut commands);
+        }
+        commands.apply(self);
+    }
+    /**
+    Get a mutable reference to a resource through a shared reference
+
+    # Safety
+
+    The caller must have exclusive access to the resource while the reference is alive
+    */
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn resource_unchecked_mut<R>(&self) -> Option<&mut R>
+    where
+        R: 'static,
+    {
+        // Safety: guaranteed by the caller
+        unsafe { self.resources.get_unchecked_mut::<R>() }
+    }
+    /**
+    Iterates over references to the given components of every `Entity` that has all of them
+
+    `Q` is either a single `Component` or a tuple of them.
+    This is what `map!(C1, C2, ... in WORLD)` expands to.
+    */
+    pub fn query<'a, Q>(&'a self) -> impl Iterator<Item = Q::Ref> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+    {
+        self.query_filtered::<Q, ()>()
+    }
+    /**
+    Iterates over mutable references to the given components of every `Entity` that has all of them
+
+    `Q` is either a single `Component` or a tuple of them.
+    This is what `map_mut!(C1, C2, ... in WORLD)` expands to.
+    */
+    pub fn query_mut<'a, Q>(&'a mut self) -> impl Iterator<Item = Q::Mut> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+    {
+        self.query_mut_filtered::<Q, ()>()
+    }
+    /**
+    Iterates over every `Entity` that has all the given components
+
+    `Q` is either a single `Component` or a tuple of them.
+    This is what `tags!(C1, C2, ... in WORLD)` expands to.
+    */
+    pub fn iter_with<'a, Q>(&'a self) -> impl Iterator<Item = EntityRef<'a, C>> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+    {
+        self.iter_with_filtered::<Q, ()>()
+    }
+    /**
+    Same as `World::query`, but only for `Entity`s that match the `Filter` `F`
+
+    Filters that depend on ticks compare against `World::last_change_tick`.
+    This is what `map!(C1, C2, ...; F1, F2, ... in WORLD)` expands to.
+    */
+    pub fn query_filtered<'a, Q, F>(&'a self) -> impl Iterator<Item = Q::Ref> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.query_since::<Q, F>(None, self.last_run)
+    }
+    /**
+    Same as `World::query_mut`, but only for `Entity`s that match the `Filter` `F`
+
+    This is what `map_mut!(C1, C2, ...; F1, F2, ... in WORLD)` expands to.
+    */
+    pub fn query_mut_filtered<'a, Q, F>(&'a mut self) -> impl Iterator<Item = Q::Mut> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Q::DISTINCT;
+        let last_run = self.last_run;
+        // Safety: the world is borrowed mutably, and `DISTINCT`
+        // guarantees that the components are all different
+        unsafe { self.query_unchecked::<Q, F>(None, last_run) }
+    }
+    /**
+    Same as `World::iter_with`, but only for `Entity`s that match the `Filter` `F`
+
+    This is what `tags!(C1, C2, ...; F1, F2, ... in WORLD)` expands to.
+    */
+    pub fn iter_with_filtered<'a, Q, F>(&'a self) -> impl Iterator<Item = EntityRef<'a, C>> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.iter_with_since::<Q, F>(None, self.last_run)
+    }
+    /// Parallel version of `World::query`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_query<'a, Q>(&'a self) -> impl ParallelIterator<Item = Q::Ref> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Ref: Send,
+    {
+        self.par_query_filtered::<Q, ()>()
+    }
+    /// Parallel version of `World::query_mut`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_query_mut<'a, Q>(&'a mut self) -> impl ParallelIterator<Item = Q::Mut> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Mut: Send,
+    {
+        self.par_query_mut_filtered::<Q, ()>()
+    }
+    /// Parallel version of `World::iter_with`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_iter_with<'a, Q>(&'a self) -> impl ParallelIterator<Item = EntityRef<'a, C>> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+    {
+        self.par_iter_with_filtered::<Q, ()>()
+    }
+    /// Parallel version of `World::query_filtered`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_query_filtered<'a, Q, F>(&'a self) -> impl ParallelIterator<Item = Q::Ref> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Ref: Send,
+        F: Filter<C> + 'a,
+    {
+        self.par_query_since::<Q, F>(None, self.last_run)
+    }
+    /// Parallel version of `World::query_mut_filtered`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_query_mut_filtered<'a, Q, F>(
+        &'a mut self,
+    ) -> impl ParallelIterator<Item = Q::Mut> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Mut: Send,
+        F: Filter<C> + 'a,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Q::DISTINCT;
+        let last_run = self.last_run;
+        // Safety: see `World::query_mut_filtered`
+        unsafe { self.par_query_unchecked::<Q, F>(None, last_run) }
+    }
+    /// Parallel version of `World::iter_with_filtered`
+    #[cfg(feature = "f_rayon")]
+    pub fn par_iter_with_filtered<'a, Q, F>(
+        &'a self,
+    ) -> impl ParallelIterator<Item = EntityRef<'a, C>> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.par_iter_with_since::<Q, F>(None, self.last_run)
+    }
+    /**
+    Start recording the `Id`s of the `Entity`s the component `T` is removed from,
+    so that they can be read with `World::removed`
+
+    Removals are only recorded for the components that are tracked, and are
+    forgotten on the next tick, i.e. by `World::advance_tick`, or when every
+    system of a `Schedule` has seen them. Outside of a `Schedule`, the removals
+    pile up until `World::advance_tick` is called. Tracking is not serialized.
+    */
+    pub fn track_removed<T>(&mut self)
+    where
+        T: Component<Enum = C>,
+    {
+        self.removed.entry(T::AS_STR).or_default();
+    }
+    /**
+    Iterates over the `Id`s of the `Entity`s the component was removed from
+    since `World::last_change_tick`
+
+    This includes `Entity`s that were removed from the `World` while
+    they had the component. Inside a `Schedule`, this is every removal
+    since the running system last ran. Removals are only recorded once the
+    component is tracked with `World::track_removed`, so this is empty before that.
+    */
+    pub fn removed<T>(&self) -> impl Iterator<Item = Id> + '_
+    where
+        T: Component<Enum = C>,
+    {
+        self.removed_since::<T>(self.last_run)
+    }
+    /**
+    Iterates over the `Id`s of the `Entity`s the component
+    was removed from after the given tick
+
+    This is empty if the component is not tracked with `World::track_removed`.
+    */
+    pub fn removed_since<T>(&self, tick: u64) -> impl Iterator<Item = Id> + '_
+    where
+        T: Component<Enum = C>,
+    {
+        self.removed
+            .get(T::AS_STR)
+            .into_iter()
+            .flatten()
+            .filter(move |(_, removed)| *removed > tick)
+            .map(|(id, _)| *id)
+    }
+    /// Get the tick that changes are currently marked with
+    pub fn change_tick(&self) -> u64 {
+        self.tick
+    }
+    /**
+    Get the tick that `Added` and `Changed` compare against
+
+    Inside a `Schedule`, this is the tick at which the running system last ran.
+    */
+    pub fn last_change_tick(&self) -> u64 {
+        self.last_run
+    }
+    /**
+    Mark every change made so far as seen
+
+    After this, `Added` and `Changed` only match components that
+    are added or changed later, and `World::removed` only returns
+    later removals. A `Schedule` keeps track of this for each system,
+    so this is only needed when querying outside of one.
+    */
+    pub fn advance_tick(&mut self) {
+        self.last_run = self.tick;
+        self.tick += 1;
+        self.forget_removed(self.last_run);
+    }
+    /// Forget the removals at or before the given tick
+    pub(crate) fn forget_removed(&mut self, tick: u64) {
+        for removed in self.removed.values_mut() {
+            removed.retain(|(_, removed)| *removed > tick);
+        }
+    }
+    /// Record that a component was removed from an `Entity`, if the component is tracked
+    fn record_removed(&mut self, name: &'static str, id: Id) {
+        if let Some(removed) = self.removed.get_mut(name) {
+            removed.push((id, self.tick));
+        }
+    }
+    /// Start a new tick and return it
+    pub(crate) fn increment_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+    /// Set the tick that `Added` and `Changed` compare against and return the old one
+    pub(crate) fn replace_last_run(&mut self, last_run: u64) -> u64 {
+        std::mem::replace(&mut self.last_run, last_run)
+    }
+    /// Same as `World::query_filtered`, but with the tick to compare against
+    pub(crate) fn query_since<'a, Q, F>(
+        &'a self,
+        archetypes: Option<&'a [usize]>,
+        last_run: u64,
+    ) -> impl Iterator<Item = Q::Ref> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.candidates::<Q, F>(archetypes, last_run)
+            .filter_map(|(row, columns)| Q::fetch(row, columns))
+    }
+    /// Same as `World::iter_with_filtered`, but with the tick to compare against
+    pub(crate) fn iter_with_since<'a, Q, F>(
+        &'a self,
+        archetypes: Option<&'a [usize]>,
+        last_run: u64,
+    ) -> impl Iterator<Item = EntityRef<'a, C>> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.candidates::<Q, F>(archetypes, last_run)
+            .filter(|(row, columns)| Q::fetch(*row, *columns).is_some())
+            .map(move |(row, _)| self.row_entity(row))
+    }
+    /**
+    Iterates over mutable references to the given components through a shared reference
+
+    # Safety
+
+    The caller must have exclusive access to the components in `Q` for `'a`,
+    and `Q` must not contain the same component more than once.
+    */
+    pub(crate) unsafe fn query_unchecked<'a, Q, F>(
+        &'a self,
+        archetypes: Option<&'a [usize]>,
+        last_run: u64,
+    ) -> impl Iterator<Item = Q::Mut> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        let tick = self.tick;
+        self.candidates::<Q, F>(archetypes, last_run)
+            // Safety: every `Entity` is visited once, and the
+            // caller guarantees exclusive access to the components
+            .filter_map(move |(row, columns)| unsafe { Q::fetch_unchecked(row, columns, tick) })
+    }
+    /// Parallel version of `World::query_since`
+    #[cfg(feature = "f_rayon")]
+    pub(crate) fn par_query_since<'a, Q, F>(
+        &'a self,
+        archetypes: Option<&'a [usize]>,
+        last_run: u64,
+    ) -> impl ParallelIterator<Item = Q::Ref> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Ref: Send,
+        F: Filter<C> + 'a,
+    {
+        self.par_candidates::<Q, F>(archetypes, last_run)
+            .filter_map(|(row, columns)| Q::fetch(row, columns))
+    }
+    /// Parallel version of `World::iter_with_since`
+    #[cfg(feature = "f_rayon")]
+    pub(crate) fn par_iter_with_since<'a, Q, F>(
+        &'a self,
+        archetypes: Option<&'a [usize]>,
+        last_run: u64,
+    ) -> impl ParallelIterator<Item = EntityRef<'a, C>> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        self.par_candidates::<Q, F>(archetypes, last_run)
+            .filter(|(row, columns)| Q::fetch(*row, *columns).is_some())
+            .map(move |(row, _)| self.row_entity(row))
+    }
+    /**
+    Parallel version of `World::query_unchecked`
+
+    # Safety
+
+    See `World::query_unchecked`
+    */
+    #[cfg(feature = "f_rayon")]
+    pub(crate) unsafe fn par_query_unchecked<'a, Q, F>(
+        &'a self,
+        archetypes: Option<&'a [usize]>,
+        last_run: u64,
+    ) -> impl ParallelIterator<Item = Q::Mut> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        Q::Mut: Send,
+        F: Filter<C> + 'a,
+    {
+        let tick = self.tick;
+        self.par_candidates::<Q, F>(archetypes, last_run)
+            // Safety: see `World::query_unchecked`
+            .filter_map(move |(row, columns)| unsafe { Q::fetch_unchecked(row, columns, tick) })
+    }
+    /**
+    Find the archetypes after the first `from` that have all of `Q`'s table components
+    and are not ruled out by the `Filter` `F`, and add their indices to `matches`
+
+    Archetypes are never removed, so a list of matches stays valid
+    and only has to be extended as new archetypes are created.
+    */
+    pub(crate) fn match_archetypes<'a, Q, F>(&'a self, from: usize, matches: &mut Vec<usize>)
+    where
+        Q: ComponentSet<'a, C>,
+        F: Filter<C>,
+    {
+        for (i, archetype) in self.archetypes.iter().enumerate().skip(from) {
+            let table = archetype.table(&self.sparse, &self.relations);
+            if Q::columns(table).is_some() && F::columns(table).is_some() {
+                matches.push(i);
+            }
+        }
+    }
+    /// Get the number of archetypes that have been created
+    pub(crate) fn archetype_count(&self) -> usize {
+        self.archetypes.len()
+    }
+    /// Get a number that is different for every `World`
+    pub(crate) fn world_id(&self) -> u64 {
+        self.world_id
+    }
+    /// Get the current generation of every `Id` index
+    #[cfg(feature = "f_snapshot")]
+    pub(crate) fn generations(&self) -> Vec<u32> {
+        self.entries.iter().map(|entry| entry.generation).collect()
+    }
+    /**
+    Get the `Id` indices that are not in use
+
+    The indices reserved by `World::reserve_id` that were never used are included,
+    so that a `World` loaded from a save can hand them out again.
+    */
+    #[cfg(feature = "f_serde")]
+    pub(crate) fn free(&self) -> Vec<u32> {
+        let reserved = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.reserved)
+            .map(|(index, _)| index as u32);
+        reserved.chain(self.free.iter().copied()).collect()
+    }
+    /**
+    Iterates over the `Entity`s that may have all the components in `Q`
+    and match the `Filter` `F`
+
+    If `Q` has a sparse component, only the `Entity`s in its sparse set are visited,
+    and the columns of every archetype are looked up before visiting them.
+    Otherwise, only the archetypes that have all of `Q`'s table components are
+    visited, which are looked up unless `archetypes` already lists them.
+    */
+    fn candidates<'a, Q, F>(
+        &'a self,
+        archetypes: Option<&'a [usize]>,
+        last_run: u64,
+    ) -> impl Iterator<Item = (Row<'a, C>, Q::Columns)> + 'a
+    where
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        if let Some(name) = Q::SPARSE {
+            let tables = self.archetype_columns::<Q, F>();
+            Either::Left(
+                self.sparse
+                    .get(name)
+                    .into_iter()
+                    .flat_map(|set| set.indices().iter())
+                    .filter_map(move |&index| {
+                        self.sparse_candidate::<Q, F>(&tables, index, last_run)
+                    }),
+            )
+        } else {
+            let archetypes = match archetypes {
+                Some(indices) => Either::Left(indices.iter().map(move |&i| &self.archetypes[i])),
+                None => Either::Right(self.archetypes.iter()),
+            };
+            Either::Right(
+                archetypes
+                    .filter_map(move |archetype| {
+                        let table = archetype.table(&self.sparse, &self.relations);
+                        Some((archetype, Q::columns(table)?, F::columns(table)?))
+                    })
+                    .flat_map(move |(archetype, columns, filter)| {
+                        (0..archetype.len())
+                            .map(move |row| archetype.row(row, &self.sparse, &self.relations))
+                            .filter(move |&row| F::matches(row, filter, last_run))
+                            .map(move |row| (row, columns))
+                    }),
+            )
+        }
+    }
+    /// Parallel version of `World::candidates`
+    #[cfg(feature = "f_rayon")]
+    fn par_candidates<'a, Q, F>(
+        &'a self,
+        archetypes: Option<&'a [usize]>,
+        last_run: u64,
+    ) -> impl ParallelIterator<Item = (Row<'a, C>, Q::Columns)> + 'a
+    where
+        C: Send + Sync,
+        Q: ComponentSet<'a, C> + 'a,
+        F: Filter<C> + 'a,
+    {
+        if let Some(name) = Q::SPARSE {
+            let tables = self.archetype_columns::<Q, F>();
+            rayon::iter::Either::Left(
+                self.sparse
+                    .get(name)
+                    .into_par_iter()
+                    .flat_map(|set| set.indices().par_iter())
+                    .filter_map(move |&index| {
+                        self.sparse_candidate::<Q, F>(&tables, index, last_run)
+                    }),
+            )
+        } else {
+            let archetypes = match archetypes {
+                Some(indices) => {
+                    rayon::iter::Either::Left(indices.par_iter().map(move |&i| &self.archetypes[i]))
+                }
+                None => rayon::iter::Either::Right(self.archetypes.par_iter()),
+            };
+            rayon::iter::Either::Right(
+                archetypes
+                    .filter_map(move |archetype| {
+                        let table = archetype.table(&self.sparse, &self.relations);
+                        Some((archetype, Q::columns(table)?, F::columns(table)?))
+                    })
+                    .flat_map(move |(archetype, columns, filter)| {
+                        (0..archetype.len())
+                            .into_par_iter()
+                            .map(move |row| archetype.row(row, &self.sparse, &self.relations))
+                            .filter(move |&row| F::matches(row, filter, last_run))
+                            .map(move |row| (row, columns))
+                    }),
+            )
+        }
+    }
+    /// Get the columns of `Q` and `F` in every archetype, or `None` for the ones without them
+    fn archetype_columns<'a, Q, F>(&'a self) -> Vec<Option<(Q::Columns, F::Columns<'a>)>>
+    where
+        Q: ComponentSet<'a, C>,
+        F: Filter<C>,
+    {
+        self.archetypes
+            .iter()
+            .map(|archetype| {
+                let table = archetype.table(&self.sparse, &self.relations);
+                Some((Q::columns(table)?, F::columns(table)?))
+            })
+            .collect()
+    }
+    /// Get the row of the `Entity` with the given `Id` index if it matches `Q` and `F`,
+    /// given the columns returned by `World::archetype_columns`
+    fn sparse_candidate<'a, Q, F>(
+        &'a self,
+        tables: &[Option<(Q::Columns, F::Columns<'a>)>],
+        index: u32,
+        last_run: u64,
+    ) -> Option<(Row<'a, C>, Q::Columns)>
+    where
+        Q: ComponentSet<'a, C>,
+        F: Filter<C>,
+    {
+        let location = self.entries[index as usize].location?;
+        let (columns, filter) = tables[location.archetype]?;
+        let row =
+            self.archetypes[location.archetype].row(location.row, &self.sparse, &self.relations);
+        if F::matches(row, filter, last_run) {
+            Some((row, columns))
+        } else {
+            None
+        }
+    }
+    /// Add an entry for a new `Id` index, and its view
+    fn push_entry(&mut self, entry: Entry) {
+        self.views.push(EntityView {
+            world: AtomicPtr::new(std::ptr::null_mut()),
+            index: self.entries.len() as u32,
+            world_type: PhantomData,
+        });
+        self.entries.push(entry);
+    }
+    fn location(&self, id: Id) -> Option<Location> {
+        self.entries
+            .get(id.index as usize)
+            .filter(|entry| entry.generation == id.generation)
+            .and_then(|entry| entry.location)
+    }
+    /// Get the `EntityRef` of the `Entity` with the given index, which must be in the `World`
+    fn entity_ref(&self, index: u32) -> EntityRef<'_, C> {
+        EntityRef { world: self, index }
+    }
+    /// Get the `EntityRef` of the `Entity` in a row of an archetype
+    fn row_entity(&self, row: Row<'_, C>) -> EntityRef<'_, C> {
+        self.entity_ref(row.id().expect("Row of a World has no id").index)
+    }
+    /// Put an `Entity` into the archetype for its components
+    ///
+    /// Its sparse components are moved to their sparse sets.
+    fn place(&mut self, id: Id, mut entity: Entity<C>) -> Location {
+        for (name, slot) in entity.sparse.drain(..) {
+            self.sparse
+                .entry(name)
+                .or_insert_with(|| SparseSet::new(slot.new_column))
+                .insert(id.index, slot);
+        }
+        let components: Box<[&'static str]> = entity.names().collect();
+        let archetypes = &mut self.archetypes;
+        let archetype = *self.archetype_ids.entry(components).or_insert_with(|| {
+            archetypes.push(Archetype::new(&entity));
+            archetypes.len() - 1
+        });
+        let row = self.archetypes[archetype].push(id, entity);
+        Location { archetype, row }
+    }
+    /// Take an `Entity` out of its archetype
+    ///
+    /// The location of the `Entity` that takes its place is updated,
+    /// but the location of the taken `Entity` is left for the caller to fix.
+    fn take(&mut self, location: Location) -> Entity<C> {
+        let archetype = &mut self.archetypes[location.archetype];
+        let entity = archetype.swap_remove(location.row);
+        if let Some(moved) = archetype.ids().get(location.row) {
+            self.entries[moved.index as usize].location = Some(location);
+        }
+        entity
+    }
+}
+
+impl<C> World<C> {
+    /// Panic with a message that tells why the `Id` is not in the `World`
+    fn missing(&self, id: Id) -> ! {
+        match self.status(id) {
+            IdStatus::Removed => panic!(
+                "Entity id {} is stale: its index has a newer generation",
+                id
+            ),
+            _ => panic!("Unable to find entity with id: {}", id),
+        }
+    }
+}
+
+impl<C> Index<Id> for World<C> {
+    type Output = EntityView<C>;
+    fn index(&self, id: Id) -> &Self::Output {
+        if self.location(id).is_none() {
+            self.missing(id)
+        }
+        let view = &self.views[id.index as usize];
+        view.world
+            .store(self as *const World<C> as *mut World<C>, Ordering::Relaxed);
+        view
+    }
+}
+
+impl<C> IndexMut<Id> for World<C> {
+    fn index_mut(&mut self, id: Id) -> &mut Self::Output {
+        if self.location(id).is_none() {
+            self.missing(id)
+        }
+        let world: *mut World<C> = self;
+        // Safety: `world` comes from `self`, and the view is borrowed through it
+        // so that the view can keep using it to read the `World`
+        let views = unsafe { &mut (*world).views };
+        let view = &mut views[id.index as usize];
+        *view.world.get_mut() = world;
+        view
+    }
+}
+
+/**
+The `Entity` that indexing a `World` with an `Id` gives
+
+`world[id][Position]` reads a component, and `world[id][Position] = value`
+changes it and marks it as changed. Adding or removing components goes through
+the `EntityMut` returned by `World::get_mut` or `World::entity_mut`.
+
+`Index` has to return a reference, so the `World` keeps a view for every `Id`
+index, and points it back at itself whenever it is indexed.
+*/
+pub struct EntityView<C> {
+    /// The `World` that owns the view, as of the last time it was indexed
+    world: AtomicPtr<World<C>>,
+    index: u32,
+    world_type: PhantomData<World<C>>,
+}
+
+// Safety: the view reads and changes the `World` like an `&World` or an `&mut World` would
+unsafe impl<C: Send + Sync> Send for EntityView<C> {}
+unsafe impl<C: Send + Sync> Sync for EntityView<C> {}
+
+impl<C> EntityView<C> {
+    /// Get the `EntityRef` of the `Entity`
+    fn entity_ref(&self) -> EntityRef<'_, C> {
+        // Safety: the view can only be borrowed through the `World` it points to,
+        // which stays borrowed and in place for as long as the view is
+        let world = unsafe { &*self.world.load(Ordering::Relaxed) };
+        world.entity_ref(self.index)
+    }
+    /// Gets the `Entity`'s id
+    pub fn id(&self) -> Id {
+        self.entity_ref().id()
+    }
+    /// Gets the `Entity`'s uuid
+    #[cfg(feature = "f_uuid")]
+    pub fn uuid(&self) -> Uuid {
+        self.entity_ref().uuid()
+    }
+    /// Get an optional reference to a component's value
+    pub fn get<T>(&self) -> Option<&T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        self.entity_ref().get::<T>()
+    }
+    /// Get an optional mutable reference to a component's value and mark it as changed
+    pub fn get_mut<T>(&mut self) -> Option<&mut T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        AsRowMut::fetch_mut::<T>(self)
+    }
+    /// Check if the `Entity` has the `Component`
+    pub fn has<T>(&self) -> bool
+    where
+        T: Component<Enum = C>,
+    {
+        self.entity_ref().has::<T>()
+    }
+}
+
+impl<C> AsRow<C> for EntityView<C> {
+    fn row(&self) -> Row<'_, C> {
+        self.entity_ref().table_row()
+    }
+}
+
+// Safety: a view is only borrowed mutably through `IndexMut`, which borrows the `World` mutably
+unsafe impl<C> AsRowMut<C> for EntityView<C> {
+    fn change_tick(&self) -> u64 {
+        self.entity_ref().world.tick
+    }
+}
+
+impl<C> fmt::Debug for EntityView<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.entity_ref().fmt(f)
+    }
+}
+
+/**
+A reference to an `Entity` in a `World`
+
+`World::get`, `World::entity` and the iterators of a `World` give these out.
+The handle is `Copy` and can read the `Entity`'s components for as long as the
+`World` is borrowed. Changing them goes through the `EntityMut` returned by
+`World::get_mut` or `World::entity_mut`, through an `EntityRefMut`, or by indexing
+a mutably borrowed `World`. Indexing the handle with a component gives its value.
+*/
+pub struct EntityRef<'a, C> {
+    world: &'a World<C>,
+    index: u32,
+}
+
+impl<'a, C> Clone for EntityRef<'a, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, C> Copy for EntityRef<'a, C> {}
+
+impl<'a, C> EntityRef<'a, C> {
+    fn location(&self) -> Location {
+        self.world.entries[self.index as usize]
+            .location
+            .expect("EntityRef of an entity that is not in the world")
+    }
+    /// Gets the `Entity`'s id
+    pub fn id(&self) -> Id {
+        Id {
+            index: self.index,
+            generation: self.world.entries[self.index as usize].generation,
+        }
+    }
+    /// Gets the `Entity`'s uuid
+    #[cfg(feature = "f_uuid")]
+    pub fn uuid(&self) -> Uuid {
+        let location = self.location();
+        self.world.archetypes[location.archetype].uuid(location.row)
+    }
+    /// Get an optional reference to a component's value
+    pub fn get<T>(&self) -> Option<&'a T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        self.table_row().get::<T>()
+    }
+    /// Get optional references to several components' values at once
+    ///
+    /// See `Entity::get_many`
+    pub fn get_many<T>(&self) -> Option<T::Ref>
+    where
+        T: ComponentSet<'a, C>,
+    {
+        self.table_row().fetch::<T>()
+    }
+    /// Check if the `Entity` has the `Component`
+    pub fn has<T>(&self) -> bool
+    where
+        T: Component<Enum = C>,
+    {
+        self.table_row().has::<T>()
+    }
+    /// Get the `Row` that the `Entity`'s components are in for as long as the `World` is borrowed
+    fn table_row(&self) -> Row<'a, C> {
+        let location = self.location();
+        self.world.archetypes[location.archetype].row(
+            location.row,
+            &self.world.sparse,
+            &self.world.relations,
+        )
+    }
+}
+
+impl<'a, C> AsRow<C> for EntityRef<'a, C> {
+    fn row(&self) -> Row<'_, C> {
+        self.table_row()
+    }
+}
+
+/// The values of the components are not in the component enum, so only their names are shown
+impl<'a, C> fmt::Debug for EntityRef<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EntityRef")
+            .field("id", &self.id())
+            .field("components", &self.row().names().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/**
+A handle to an `Entity` in a `World` that can change the values of its components
+
+`World::iter_mut` and the parallel iterator of a `&mut World` give these out,
+one for each `Entity`. Unlike an `EntityRef`, the references it gives out
+only live as long as the handle is borrowed.
+*/
+pub struct EntityRefMut<'a, C> {
+    world: &'a World<C>,
+    index: u32,
+}
+
+impl<'a, C> EntityRefMut<'a, C> {
+    /// Gets the `Entity`'s id
+    pub fn id(&self) -> Id {
+        self.world.entity_ref(self.index).id()
+    }
+    /// Gets the `Entity`'s uuid
+    #[cfg(feature = "f_uuid")]
+    pub fn uuid(&self) -> Uuid {
+        self.world.entity_ref(self.index).uuid()
+    }
+    /// Get an optional reference to a component's value
+    pub fn get<T>(&self) -> Option<&T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        self.row().get::<T>()
+    }
+    /// Get an optional mutable reference to a component's value and mark it as changed
+    pub fn get_mut<T>(&mut self) -> Option<&mut T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        AsRowMut::fetch_mut::<T>(self)
+    }
+    /// Get optional references to several components' values at once
+    ///
+    /// See `Entity::get_many`
+    pub fn get_many<'b, T>(&'b self) -> Option<T::Ref>
+    where
+        T: ComponentSet<'b, C>,
+    {
+        AsRow::fetch::<T>(self)
+    }
+    /// Get optional mutable references to several components' values at once
+    /// and mark them as changed
+    ///
+    /// See `Entity::get_many_mut`
+    pub fn get_many_mut<'b, T>(&'b mut self) -> Option<T::Mut>
+    where
+        T: ComponentSet<'b, C>,
+    {
+        AsRowMut::fetch_mut::<T>(self)
+    }
+    /// Check if the `Entity` has the `Component`
+    pub fn has<T>(&self) -> bool
+    where
+        T: Component<Enum = C>,
+    {
+        self.row().has::<T>()
+    }
+}
+
+impl<'a, C> AsRow<C> for EntityRefMut<'a, C> {
+    fn row(&self) -> Row<'_, C> {
+        self.world.entity_ref(self.index).table_row()
+    }
+}
+
+// Safety: the handles are only created by the iterators of a mutably borrowed
+// `World`, which give out one handle for each `Entity`
+unsafe impl<'a, C> AsRowMut<C> for EntityRefMut<'a, C> {
+    fn change_tick(&self) -> u64 {
+        self.world.tick
+    }
+}
+
+impl<'a, C> fmt::Debug for EntityRefMut<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.world.entity_ref(self.index).fmt(f)
+    }
+}
+
+/**
+A mutable handle to an `Entity` in a `World`
+
+Created with `World::get_mut` or `World::entity_mut`. The commands recorded by the hooks
+of the components added and removed through the handle are applied
+when it is dropped.
+*/
+pub struct EntityMut<'a, C> {
+    world: &'a mut World<C>,
+    id: Id,
+    /// The commands recorded by hooks
+    commands: Commands<C>,
+}
+
+impl<'a, C> EntityMut<'a, C> {
+    /// Gets the `Entity`'s id
+    pub fn id(&self) -> Id {
+        self.id
+    }
+    fn location(&self) -> Location {
+        self.world.entries[self.id.index as usize]
+            .location
+            .expect("EntityMut of an entity that is not in the world")
+    }
+    /// Gets the `Entity`'s uuid
+    #[cfg(feature = "f_uuid")]
+    pub fn uuid(&self) -> Uuid {
+        self.world.entity_ref(self.id.index).uuid()
+    }
+    /// Get an optional reference to a component's value
+    pub fn get<T>(&self) -> Option<&T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        self.row().get::<T>()
+    }
+    /// Get an optional mutable reference to a component's value and mark it as changed
+    pub fn get_mut<T>(&mut self) -> Option<&mut T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        AsRowMut::fetch_mut::<T>(self)
+    }
+    /// Get optional references to several components' values at once
+    ///
+    /// See `Entity::get_many`
+    pub fn get_many<'b, T>(&'b self) -> Option<T::Ref>
+    where
+        T: ComponentSet<'b, C>,
+    {
+        AsRow::fetch::<T>(self)
+    }
+    /// Get optional mutable references to several components' values at once
+    /// and mark them as changed
+    ///
+    /// See `Entity::get_many_mut`
+    pub fn get_many_mut<'b, T>(&'b mut self) -> Option<T::Mut>
+    where
+        T: ComponentSet<'b, C>,
+    {
+        AsRowMut::fetch_mut::<T>(self)
+    }
+    /// Check if the `Entity` has the `Component`
+    pub fn has<T>(&self) -> bool
+    where
+        T: Component<Enum = C>,
+    {
+        self.row().has::<T>()
+    }
+    /// Add a `Component` to the `Entity`
+    pub fn add<T>(&mut self, value: T::Type) -> Option<T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        let tick = self.world.tick;
+        if let Some(old) = self.get_mut::<T>() {
+            let old = std::mem::replace(old, value);
+            self.run_hooks(HookKind::Insert, T::AS_STR);
+            return Some(old);
+        }
+        if T::STORAGE == Storage::Sparse {
+            let mut slot = Slot::new::<T>(value);
+            slot.stamp(tick);
+            let sparse = self
+                .world
+                .sparse
+                .entry(T::AS_STR)
+                .or_insert_with(|| SparseSet::new(new_column::<T>));
+            sparse.insert(self.id.index, slot);
+        } else {
+            self.relocate(|entity| entity.add_at::<T>(value, tick));
+        }
+        self.run_hooks(HookKind::Add, T::AS_STR);
+        self.run_hooks(HookKind::Insert, T::AS_STR);
+        None
+    }
+    /// Remove a `Component` from the `Entity`
+    pub fn remove<T>(&mut self) -> Option<T::Type>
+    where
+        T: Component<Enum = C>,
+    {
+        if !self.has::<T>() {
+            return None;
+        }
+        self.run_hooks(HookKind::Remove, T::AS_STR);
+        self.world.record_removed(T::AS_STR, self.id);
+        if T::STORAGE == Storage::Sparse {
+            let slot = self
+                .world
+                .sparse
+                .get_mut(T::AS_STR)?
+                .remove(self.id.index)?;
+            Some(T::enum_to_val(slot.into_inner()))
+        } else {
+            self.relocate(|entity| entity.remove::<T>())
+        }
+    }
+    /// Change the `Entity`'s components and move it to its new archetype
+    fn relocate<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Entity<C>) -> R,
+    {
+        let mut entity = self.world.take(self.location());
+        let result = f(&mut entity);
+        let location = self.world.place(self.id, entity);
+        self.world.entries[self.id.index as usize].location = Some(location);
+        result
+    }
+    /// Run the hooks of the given kind for one of the `Entity`'s components
+    fn run_hooks(&mut self, kind: HookKind, name: &'static str) {
+        let row = self.world.entity_ref(self.id.index).table_row();
+        self.world
+            .hooks
+            .run(kind, name, self.id, row, &mut self.commands);
+    }
+}
+
+impl<'a, C> Drop for EntityMut<'a, C> {
+    fn drop(&mut self) {
+        if !self.commands.is_empty() {
+            std::mem::take(&mut self.commands).apply(self.world);
+        }
+    }
+}
+
+impl<'a, C> AsRow<C> for EntityMut<'a, C> {
+    fn row(&self) -> Row<'_, C> {
+        self.world.entity_ref(self.id.index).table_row()
+    }
+}
+
+// Safety: the handle borrows the `World` mutably
+unsafe impl<'a, C> AsRowMut<C> for EntityMut<'a, C> {
+    fn change_tick(&self) -> u64 {
+        self.world.tick
+    }
+}
+
+impl<'a, C> fmt::Debug for EntityMut<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.world.entity_ref(self.id.index).fmt(f)
+    }
+}
+
+/// An iterator over the `Entity`s in a `World`
+pub struct Iter<'a, C> {
+    world: &'a World<C>,
+    archetypes: std::slice::Iter<'a, Archetype<C>>,
+    ids: std::slice::Iter<'a, Id>,
+}
+
+impl<'a, C> Iterator for Iter<'a, C> {
+    type Item = EntityRef<'a, C>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(id) = self.ids.next() {
+                return Some(self.world.entity_ref(id.index));
+            }
+            self.ids = self.archetypes.next()?.ids().iter();
+        }
+    }
+}
+
+/// An iterator over handles that can change the components of the `Entity`s in a `World`
+pub struct IterMut<'a, C> {
+    world: &'a World<C>,
+    archetypes: std::slice::Iter<'a, Archetype<C>>,
+    ids: std::slice::Iter<'a, Id>,
+}
+
+impl<'a, C> Iterator for IterMut<'a, C> {
+    type Item = EntityRefMut<'a, C>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(id) = self.ids.next() {
+                return Some(EntityRefMut {
+                    world: self.world,
+                    index: id.index,
+                });
+            }
+            self.ids = self.archetypes.next()?.ids().iter();
+        }
+    }
+}
+
+/// A parallel iterator over the `Entity`s in a `World`
+#[cfg(feature = "f_rayon")]
+pub struct ParIter<'a, C> {
+    world: &'a World<C>,
+}
+
+#[cfg(feature = "f_rayon")]
+impl<'a, C> ParallelIterator for ParIter<'a, C>
+where
+    C: Send + Sync,
+{
+    type Item = EntityRef<'a, C>;
+    fn drive_unindexed<U>(self, consumer: U) -> U::Result
+    where
+        U: rayon::iter::plumbing::UnindexedConsumer<Self::Item>,
+    {
+        let world = self.world;
+        world
+            .archetypes
+            .par_iter()
+            .flat_map(|archetype| archetype.ids().par_iter())
+            .map(move |id| world.entity_ref(id.index))
+            .drive_unindexed(consumer)
+    }
+}
+
+#[cfg(feature = "f_rayon")]
+impl<'a, C> IntoParallelIterator for &'a World<C>
+where
+    C: Send + Sync,
+{
+    type Item = EntityRef<'a, C>;
+    type Iter = ParIter<'a, C>;
+    fn into_par_iter(self) -> Self::Iter {
+        ParIter { world: self }
+    }
+}
+
+/// A parallel iterator over handles that can change the components of the `Entity`s in a `World`
+#[cfg(feature = "f_rayon")]
+pub struct ParIterMut<'a, C> {
+    world: &'a World<C>,
+}
+
+#[cfg(feature = "f_rayon")]
+impl<'a, C> ParallelIterator for ParIterMut<'a, C>
+where
+    C: Send + Sync,
+{
+    type Item = EntityRefMut<'a, C>;
+    fn drive_unindexed<U>(self, consumer: U) -> U::Result
+    where
+        U: rayon::iter::plumbing::UnindexedConsumer<Self::Item>,
+    {
+        let world = self.world;
+        world
+            .archetypes
+            .par_iter()
+            .flat_map(|archetype| archetype.ids().par_iter())
+            .map(move |id| EntityRefMut {
+                world,
+                index: id.index,
+            })
+            .drive_unindexed(consumer)
+    }
+}
+
+#[cfg(feature = "f_rayon")]
+impl<'a, C> IntoParallelIterator for &'a mut World<C>
+where
+    C: Send + Sync,
+{
+    type Item = EntityRefMut<'a, C>;
+    type Iter = ParIterMut<'a, C>;
+    fn into_par_iter(self) -> Self::Iter {
+        ParIterMut { world: self }
+    }
+}
+
+/// Serializes the `Entity`s of a `World` as a map from their `Id`s
+#[cfg(feature = "f_serde")]
+struct SerEntities<'a, C>(&'a World<C>);
+
+#[cfg(feature = "f_serde")]
+impl<'a, C> Serialize for SerEntities<'a, C>
+where
+    C: SerdeComponents,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.0.iter().map(|entity| (entity.id(), entity)))
+    }
+}
+
+/// Serializes the children of the `Entity`s of a `World` that have any
+#[cfg(feature = "f_serde")]
+struct SerChildren<'a, C>(&'a World<C>);
+
+#[cfg(feature = "f_serde")]
+impl<'a, C> Serialize for SerChildren<'a, C> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let world = self.0;
+        serializer.collect_map(
+            world
+                .iter()
+                .map(|entity| entity.id())
+                .map(|id| (id, world.children(id)))
+                .filter(|(_, children)| !children.is_empty()),
+        )
+    }
+}
+
+/**
+`World`s are serialized together with the `Id`s of their `Entity`s
+and the generations of the unused `Id` indices, so `Id`s stored in
+components keep referring to the same `Entity`s after deserializing,
+and stale `Id`s stay stale. Parents and children are kept as well.
+Resources, relations, change ticks and removed components are not serialized.
+*/
+#[cfg(feature = "f_serde")]
+impl<C> Serialize for World<C>
+where
+    C: SerdeComponents,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let generations: Vec<u32> = self.entries.iter().map(|entry| entry.generation).collect();
+        let mut state = serializer.serialize_struct("World", 4)?;
+        state.serialize_field("generations", &generations)?;
+        state.serialize_field("free", &self.free())?;
+        state.serialize_field("entities", &SerEntities(self))?;
+        state.serialize_field("children", &SerChildren(self))?;
+        state.end()
+    }
+}
+
+/// The serialized form of a `World`
+#[cfg(feature = "f_serde")]
+#[derive(Deserialize)]
+#[serde(bound = "C: SerdeComponents")]
+pub(crate) struct WorldData<C> {
+    pub(crate) generations: Vec<u32>,
+    pub(crate) free: Vec<u32>,
+    pub(crate) entities: OrderedMap<Id, Entity<C>>,
+    pub(crate) children: OrderedMap<Id, Vec<Id>>,
+}
+
+#[cfg(feature = "f_serde")]
+impl<'de, C> Deserialize<'de> for World<C>
+where
+    C: SerdeComponents,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = WorldData::<C>::deserialize(deserializer)?;
+        World::from_data(data).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "f_serde")]
+impl<C> World<C> {
+    /// Rebuild a `World` from its serialized form, checking that the `Id`s are consistent
+    pub(crate) fn from_data(data: WorldData<C>) -> Result<World<C>, String> {
+        let mut world = World::new();
+        for generation in data.generations {
+            world.push_entry(Entry {
+                generation,
+                location: None,
+                reserved: false,
+            });
+        }
+        for (id, entity) in data.entities.0 {
+            match world.entries.get(id.index as usize) {
+                Some(entry) if entry.generation == id.generation && entry.location.is_none() => {}
+                _ => return Err(format!("Entity id {} is invalid or used twice", id)),
+            }
+            world.insert_at(id, entity, &mut Commands::new());
+        }
+        for &index in &data.free {
+            match world.entries.get(index as usize) {
+                Some(entry) if entry.location.is_none() => {}
+                _ => return Err(format!("Free index {} is in use", index)),
+            }
+        }
+        let mut free = data.free.clone();
+        free.sort_unstable();
+        free.dedup();
+        if free.len() != data.free.len() || free.len() + world.len != world.entries.len() {
+            return Err("The free indices don't match the entities".into());
+        }
+        world.free = data.free;
+        for (parent, children) in data.children.0 {
+            for child in children {
+                if !world.set_parent(child, parent) {
+                    return Err(format!("Entity {} cannot be a child of {}", child, parent));
+                }
+            }
+        }
+        Ok(world)
+    }
+}